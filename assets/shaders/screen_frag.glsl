@@ -6,11 +6,16 @@ uniform vec2 resolution;
 uniform sampler2D texture;
 uniform vec2 scale;
 uniform vec2 viewport;
+// scene transitions: how much the color covers the whole screen, and the screen from the left
+uniform float fade;
+uniform float wipe;
+uniform vec4 transition_color;
 
 void main() {
 
     vec2 uv = (gl_FragCoord.xy - viewport.xy) / resolution.xy * scale;
-    gl_FragColor = texture2D( texture, uv );
+    float wiped = 1.0 - step(wipe, uv.x);
+    gl_FragColor = mix( texture2D( texture, uv ), transition_color, max(fade, wiped) );
 
 }
 
@@ -0,0 +1,121 @@
+//! Compares the component storages on the bunnymark workload from `engine.rs`.
+//! Run with `cargo bench --bench storages`.
+
+extern crate cgmath;
+extern crate minigame;
+extern crate rand;
+
+use cgmath::Vector2;
+use minigame::component::Component;
+use minigame::storage::{DenseVecStorage, NullStorage, VecStorage};
+use minigame::world::World;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const BUNNIES: usize = 10_000;
+const FRAMES: usize = 100;
+
+macro_rules! bunny_component {
+    ($name:ident, $storage:ty) => {
+        struct $name {
+            position: Vector2<f32>,
+            speed: Vector2<f64>,
+            gravity: f64,
+        }
+
+        impl Component for $name {
+            type Storage = $storage;
+        }
+
+        impl $name {
+            fn new() -> Self {
+                let mut rng = rand::thread_rng();
+                $name {
+                    position: Vector2::new(0.0, 0.0),
+                    speed: Vector2::new(rng.gen::<f64>() * 500.0, (rng.gen::<f64>() * 500.0) - 250.0),
+                    gravity: 0.5 * 100.0,
+                }
+            }
+
+            fn update(&mut self, dt: f64) {
+                self.position.x += (self.speed.x * dt) as f32;
+                self.position.y += (self.speed.y * dt) as f32;
+                self.speed.y += self.gravity;
+
+                if self.position.x > 800.0 {
+                    self.speed.x *= -1.0;
+                    self.position.x = 800.0;
+                } else if self.position.x < 0.0 {
+                    self.speed.x *= -1.0;
+                    self.position.x = 0.0;
+                }
+
+                if self.position.y > 600.0 {
+                    self.speed.y *= -0.8;
+                    self.position.y = 600.0;
+                } else if self.position.y < 0.0 {
+                    self.speed.y = 0.0;
+                    self.position.y = 0.0;
+                }
+            }
+        }
+    }
+}
+
+bunny_component!(HashMapBunny, HashMap<usize, Self>);
+bunny_component!(VecBunny, VecStorage<Self>);
+bunny_component!(DenseVecBunny, DenseVecStorage<Self>);
+
+#[derive(Default)]
+struct Visible;
+
+impl Component for Visible {
+    type Storage = NullStorage<Self>;
+}
+
+macro_rules! bench_storage {
+    ($name:ident, $label:expr) => {{
+        let mut world: World<()> = World::new();
+        world.register_component_with_storage::<$name>();
+        world.register_component_with_storage::<Visible>();
+        for i in 0..BUNNIES {
+            let entity = world.create_entity();
+            world.add_component_to_entity(entity, $name::new());
+            // only every tenth bunny is visible so the marker storage drives the second query
+            if i % 10 == 0 {
+                world.add_component_to_entity(entity, Visible);
+            }
+        }
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            world.query_mut::<&mut $name>().for_each(|_, bunny| {
+                bunny.update(1.0 / 60.0);
+            });
+        }
+        let all = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            world.query_mut::<(&mut $name, &Visible)>().for_each(|_, (bunny, _)| {
+                bunny.update(1.0 / 60.0);
+            });
+        }
+        let visible = start.elapsed();
+
+        report($label, all, visible);
+    }}
+}
+
+fn report(label: &str, all: Duration, visible: Duration) {
+    let per_frame = |d: Duration| (d.as_secs() as f64 * 1000.0 + d.subsec_nanos() as f64 / 1_000_000.0) / FRAMES as f64;
+    println!("{:<16} all bunnies: {:>8.3} ms/frame   visible bunnies: {:>8.3} ms/frame", label, per_frame(all), per_frame(visible));
+}
+
+fn main() {
+    println!("{} bunnies, {} frames", BUNNIES, FRAMES);
+    bench_storage!(HashMapBunny, "HashMap");
+    bench_storage!(VecBunny, "VecStorage");
+    bench_storage!(DenseVecBunny, "DenseVecStorage");
+}
@@ -0,0 +1,37 @@
+use atlas::texturepacker::Spritesheet;
+use rectangle::Rectangle;
+use std::collections::HashMap;
+use std::string::String;
+
+/// The texture atlases of the game by id, each with the id of its texture in the TextureManager.
+pub struct AtlasManager {
+    items: HashMap<String, (String, Spritesheet)>,
+}
+
+impl AtlasManager {
+    pub fn new() -> AtlasManager {
+        AtlasManager {
+            items: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, id: String, texture_id: String, spritesheet: Spritesheet) {
+        self.items.insert(id, (texture_id, spritesheet));
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Spritesheet> {
+        self.items.get(id).map(|item| &item.1)
+    }
+
+    pub fn get_texture_id(&self, id: &str) -> Option<&str> {
+        self.items.get(id).map(|item| item.0.as_str())
+    }
+
+    /// Texture id and source rectangle of a frame of the atlas.
+    pub fn get_frame(&self, id: &str, filename: &str) -> Option<(&str, Rectangle)> {
+        let &(ref texture_id, ref spritesheet) = self.items.get(id)?;
+        let frame = spritesheet.frames.iter().find(|frame| frame.filename == filename)?;
+        let rect = Rectangle::new(frame.frame.x as f32, frame.frame.y as f32, frame.frame.w as i32, frame.frame.h as i32);
+        Some((texture_id.as_str(), rect))
+    }
+}
@@ -1 +1,2 @@
-pub mod texturepacker;
\ No newline at end of file
+pub mod texturepacker;
+pub mod atlasmanager;
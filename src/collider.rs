@@ -2,11 +2,17 @@ extern crate cgmath;
 
 use rectangle::Rectangle;
 use self::cgmath::{InnerSpace, MetricSpace, Vector2};
+use std::cmp::Ordering;
 use utils::{Clamp};
+use log::Log;
 
+/// Circle centered on `origin`, relative to the position of the entity it belongs to.
+/// A collider that doesn't belong to an entity is placed at its origin.
+#[derive(Debug, Copy, Clone)]
 pub struct CircleCollider {
     radius: f32,
     origin: Vector2<f32>,
+    position: Vector2<f32>,
 }
 
 impl CircleCollider {
@@ -14,27 +20,37 @@ impl CircleCollider {
         CircleCollider {
             radius: radius,
             origin: Vector2::new(origin.x, origin.y),
+            position: Vector2::new(0.0, 0.0),
         }
     }
 
-    pub fn get_absolute_position(&self) -> Vector2<f32> {
+    pub fn get_origin(&self) -> Vector2<f32> {
         self.origin
     }
 
+    /// Moves the collider along with the entity it belongs to.
+    pub fn set_entity_position(&mut self, position: Vector2<f32>) {
+        self.position = position;
+    }
+
+    pub fn get_absolute_position(&self) -> Vector2<f32> {
+        self.position + self.origin
+    }
+
     pub fn get_absolute_left(&self) -> f32 {
-        self.origin.x - self.radius
+        self.get_absolute_position().x - self.radius
     }
 
     pub fn get_absolute_right(&self) -> f32 {
-        self.origin.x + self.radius
+        self.get_absolute_position().x + self.radius
     }
 
     pub fn get_absolute_top(&self) -> f32 {
-        self.origin.y - self.radius
+        self.get_absolute_position().y - self.radius
     }
 
     pub fn get_absolute_bottom(&self) -> f32 {
-        self.origin.y + self.radius
+        self.get_absolute_position().y + self.radius
     }
 
     pub fn get_radius(&self) -> f32 {
@@ -42,10 +58,14 @@ impl CircleCollider {
     }
 }
 
+/// Axis aligned box whose top left corner is at `origin`, relative to the position
+/// of the entity it belongs to.
+#[derive(Debug, Copy, Clone)]
 pub struct BoxCollider {
     origin: Vector2<f32>,
     width: f32,
     height: f32,
+    position: Vector2<f32>,
 }
 
 impl BoxCollider {
@@ -54,34 +74,358 @@ impl BoxCollider {
             origin: origin,
             width: width,
             height: height,
+            position: Vector2::new(0.0, 0.0),
         }
     }
 
-    pub fn get_absolute_position(&self) -> Vector2<f32> {
+    pub fn get_origin(&self) -> Vector2<f32> {
         self.origin
     }
 
+    pub fn get_width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> f32 {
+        self.height
+    }
+
+    /// Moves the collider along with the entity it belongs to.
+    pub fn set_entity_position(&mut self, position: Vector2<f32>) {
+        self.position = position;
+    }
+
+    pub fn get_absolute_position(&self) -> Vector2<f32> {
+        self.position + self.origin
+    }
+
     pub fn get_absolute_left(&self) -> f32 {
-        self.origin.x
+        self.get_absolute_position().x
     }
 
     pub fn get_absolute_right(&self) -> f32 {
-        self.origin.x + self.width
+        self.get_absolute_position().x + self.width
     }
 
     pub fn get_absolute_top(&self) -> f32 {
-        self.origin.y
+        self.get_absolute_position().y
     }
 
     pub fn get_absolute_bottom(&self) -> f32 {
-        self.origin.y + self.height
+        self.get_absolute_position().y + self.height
     }
 
     pub fn as_rect(&self) -> Rectangle {
-        let rect = Rectangle::new(self.origin.x, self.origin.y, self.width as i32, self.height as i32);
+        let position = self.get_absolute_position();
+        let rect = Rectangle::new(position.x, position.y, self.width as i32, self.height as i32);
         return rect;
     }
 
+    /// Corners in clockwise order, starting from the top left one.
+    pub fn get_corners(&self) -> [Vector2<f32>; 4] {
+        let left = self.get_absolute_left();
+        let right = self.get_absolute_right();
+        let top = self.get_absolute_top();
+        let bottom = self.get_absolute_bottom();
+        [Vector2::new(left, top), Vector2::new(right, top), Vector2::new(right, bottom), Vector2::new(left, bottom)]
+    }
+
+}
+
+/// Convex polygon whose points are relative to `origin`, itself relative to the position
+/// of the entity it belongs to. The polygon is rotated around its origin by its own rotation,
+/// then around the entity by the entity's rotation. Points can be in either winding order.
+#[derive(Debug, Clone)]
+pub struct PolygonCollider {
+    points: Vec<Vector2<f32>>,
+    origin: Vector2<f32>,
+    rotation: f32,
+    position: Vector2<f32>,
+    entity_rotation: f32,
+}
+
+impl PolygonCollider {
+    /// None if there are fewer than 3 points, if two consecutive points are the same
+    /// or if the points are on a line.
+    pub fn new(origin: Vector2<f32>, points: Vec<Vector2<f32>>) -> Option<Self> {
+        if points.len() < 3 {
+            Log::warning("A polygon collider needs at least 3 points");
+            return None;
+        }
+        let count = points.len();
+        if (0..count).any(|i| points[i] == points[(i + 1) % count]) {
+            Log::warning("A polygon collider cannot have sides of zero length");
+            return None;
+        }
+        if signed_area(&points).abs() < 1e-6 {
+            Log::warning("A polygon collider cannot have its points on a line");
+            return None;
+        }
+        Some(PolygonCollider::from_points(origin, points))
+    }
+
+    fn from_points(origin: Vector2<f32>, points: Vec<Vector2<f32>>) -> Self {
+        PolygonCollider {
+            points: points,
+            origin: origin,
+            rotation: 0.0,
+            position: Vector2::new(0.0, 0.0),
+            entity_rotation: 0.0,
+        }
+    }
+
+    /// Box of the given size centered on `origin` and rotated by `rotation` radians.
+    pub fn oriented_box(origin: Vector2<f32>, width: f32, height: f32, rotation: f32) -> Self {
+        let w = width / 2.0;
+        let h = height / 2.0;
+        let points = vec![Vector2::new(-w, -h), Vector2::new(w, -h), Vector2::new(w, h), Vector2::new(-w, h)];
+        PolygonCollider::from_points(origin, points).with_rotation(rotation)
+    }
+
+    /// Polygon object of a Tiled map: the points are relative to the object position and
+    /// the object is rotated clockwise around it by `rotation` degrees.
+    pub fn from_tiled_polygon(x: f32, y: f32, rotation: f32, points: Vec<Vector2<f32>>) -> Option<Self> {
+        let polygon = PolygonCollider::new(Vector2::new(x, y), points)?.with_rotation(rotation.to_radians());
+        if !polygon.is_convex() {
+            Log::warning("Tiled polygon is not convex, its collisions won't be accurate");
+        }
+        Some(polygon)
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn get_points(&self) -> &[Vector2<f32>] {
+        &self.points
+    }
+
+    pub fn get_origin(&self) -> Vector2<f32> {
+        self.origin
+    }
+
+    pub fn get_rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
+    /// Moves the collider along with the entity it belongs to.
+    pub fn set_entity_position(&mut self, position: Vector2<f32>) {
+        self.position = position;
+    }
+
+    /// Turns the collider along with the entity it belongs to.
+    pub fn set_entity_rotation(&mut self, rotation: f32) {
+        self.entity_rotation = rotation;
+    }
+
+    pub fn get_absolute_points(&self) -> Vec<Vector2<f32>> {
+        self.points.iter()
+            .map(|point| self.position + rotate(self.origin + rotate(*point, self.rotation), self.entity_rotation))
+            .collect()
+    }
+
+    pub fn get_absolute_left(&self) -> f32 {
+        self.get_absolute_points().iter().fold(::std::f32::INFINITY, |left, point| left.min(point.x))
+    }
+
+    pub fn get_absolute_right(&self) -> f32 {
+        self.get_absolute_points().iter().fold(::std::f32::NEG_INFINITY, |right, point| right.max(point.x))
+    }
+
+    pub fn get_absolute_top(&self) -> f32 {
+        self.get_absolute_points().iter().fold(::std::f32::INFINITY, |top, point| top.min(point.y))
+    }
+
+    pub fn get_absolute_bottom(&self) -> f32 {
+        self.get_absolute_points().iter().fold(::std::f32::NEG_INFINITY, |bottom, point| bottom.max(point.y))
+    }
+
+    /// Whether every turn along the outline goes the same way.
+    pub fn is_convex(&self) -> bool {
+        let count = self.points.len();
+        if count < 3 {
+            return false;
+        }
+        let mut sign = 0.0;
+        for i in 0..count {
+            let a = self.points[i];
+            let b = self.points[(i + 1) % count];
+            let c = self.points[(i + 2) % count];
+            let cross = (b.x - a.x) * (c.y - b.y) - (b.y - a.y) * (c.x - b.x);
+            if cross != 0.0 {
+                if sign != 0.0 && cross.signum() != sign {
+                    return false;
+                }
+                sign = cross.signum();
+            }
+        }
+        true
+    }
+}
+
+fn rotate(point: Vector2<f32>, angle: f32) -> Vector2<f32> {
+    if angle == 0.0 {
+        return point;
+    }
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(point.x * cos - point.y * sin, point.x * sin + point.y * cos)
+}
+
+/// One of the shapes a ColliderList is made of.
+#[derive(Debug, Clone)]
+pub enum ColliderShape {
+    Box(BoxCollider),
+    Circle(CircleCollider),
+    Polygon(PolygonCollider),
+}
+
+impl ColliderShape {
+    pub fn set_entity_position(&mut self, position: Vector2<f32>) {
+        match *self {
+            ColliderShape::Box(ref mut box_collider) => box_collider.set_entity_position(position),
+            ColliderShape::Circle(ref mut circle) => circle.set_entity_position(position),
+            ColliderShape::Polygon(ref mut polygon) => polygon.set_entity_position(position),
+        }
+    }
+
+    /// Only polygons turn with their entity, boxes stay axis aligned and circles don't mind.
+    pub fn set_entity_rotation(&mut self, rotation: f32) {
+        match *self {
+            ColliderShape::Polygon(ref mut polygon) => polygon.set_entity_rotation(rotation),
+            _ => {}
+        }
+    }
+
+    pub fn get_absolute_left(&self) -> f32 {
+        match *self {
+            ColliderShape::Box(ref box_collider) => box_collider.get_absolute_left(),
+            ColliderShape::Circle(ref circle) => circle.get_absolute_left(),
+            ColliderShape::Polygon(ref polygon) => polygon.get_absolute_left(),
+        }
+    }
+
+    pub fn get_absolute_right(&self) -> f32 {
+        match *self {
+            ColliderShape::Box(ref box_collider) => box_collider.get_absolute_right(),
+            ColliderShape::Circle(ref circle) => circle.get_absolute_right(),
+            ColliderShape::Polygon(ref polygon) => polygon.get_absolute_right(),
+        }
+    }
+
+    pub fn get_absolute_top(&self) -> f32 {
+        match *self {
+            ColliderShape::Box(ref box_collider) => box_collider.get_absolute_top(),
+            ColliderShape::Circle(ref circle) => circle.get_absolute_top(),
+            ColliderShape::Polygon(ref polygon) => polygon.get_absolute_top(),
+        }
+    }
+
+    pub fn get_absolute_bottom(&self) -> f32 {
+        match *self {
+            ColliderShape::Box(ref box_collider) => box_collider.get_absolute_bottom(),
+            ColliderShape::Circle(ref circle) => circle.get_absolute_bottom(),
+            ColliderShape::Polygon(ref polygon) => polygon.get_absolute_bottom(),
+        }
+    }
+
+    pub fn collide(&self, other: &ColliderShape) -> bool {
+        match (self, other) {
+            (&ColliderShape::Box(ref a), &ColliderShape::Box(ref b)) => Collider::collide_box_to_box(a, b),
+            (&ColliderShape::Box(ref a), &ColliderShape::Circle(ref b)) => Collider::collide_circle_to_box(b, a),
+            (&ColliderShape::Circle(ref a), &ColliderShape::Box(ref b)) => Collider::collide_circle_to_box(a, b),
+            (&ColliderShape::Circle(ref a), &ColliderShape::Circle(ref b)) => Collider::collide_circle_to_circle(a, b),
+            (&ColliderShape::Polygon(ref a), &ColliderShape::Polygon(ref b)) => Collider::collide_polygon_to_polygon(a, b),
+            (&ColliderShape::Polygon(ref a), &ColliderShape::Box(ref b)) => Collider::collide_polygon_to_box(a, b),
+            (&ColliderShape::Box(ref a), &ColliderShape::Polygon(ref b)) => Collider::collide_polygon_to_box(b, a),
+            (&ColliderShape::Polygon(ref a), &ColliderShape::Circle(ref b)) => Collider::collide_polygon_to_circle(a, b),
+            (&ColliderShape::Circle(ref a), &ColliderShape::Polygon(ref b)) => Collider::collide_polygon_to_circle(b, a),
+        }
+    }
+
+    /// How the shapes overlap, the normal going from this shape towards the other one.
+    pub fn collide_manifold(&self, other: &ColliderShape) -> Option<CollisionManifold> {
+        match (self, other) {
+            (&ColliderShape::Box(ref a), &ColliderShape::Box(ref b)) => Collider::collide_box_to_box_manifold(a, b),
+            (&ColliderShape::Box(ref a), &ColliderShape::Circle(ref b)) => Collider::collide_circle_to_box_manifold(b, a).map(|m| m.reversed()),
+            (&ColliderShape::Circle(ref a), &ColliderShape::Box(ref b)) => Collider::collide_circle_to_box_manifold(a, b),
+            (&ColliderShape::Circle(ref a), &ColliderShape::Circle(ref b)) => Collider::collide_circle_to_circle_manifold(a, b),
+            (&ColliderShape::Polygon(ref a), &ColliderShape::Polygon(ref b)) => Collider::collide_polygon_to_polygon_manifold(a, b),
+            (&ColliderShape::Polygon(ref a), &ColliderShape::Box(ref b)) => Collider::collide_polygon_to_box_manifold(a, b),
+            (&ColliderShape::Box(ref a), &ColliderShape::Polygon(ref b)) => Collider::collide_polygon_to_box_manifold(b, a).map(|m| m.reversed()),
+            (&ColliderShape::Polygon(ref a), &ColliderShape::Circle(ref b)) => Collider::collide_polygon_to_circle_manifold(a, b),
+            (&ColliderShape::Circle(ref a), &ColliderShape::Polygon(ref b)) => Collider::collide_polygon_to_circle_manifold(b, a).map(|m| m.reversed()),
+        }
+    }
+
+    /// See `Collider::raycast_box`.
+    pub fn raycast(&self, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+        match *self {
+            ColliderShape::Box(ref box_collider) => Collider::raycast_box(box_collider, origin, direction, max_distance),
+            ColliderShape::Circle(ref circle) => Collider::raycast_circle(circle, origin, direction, max_distance),
+            ColliderShape::Polygon(ref polygon) => Collider::raycast_polygon(polygon, origin, direction, max_distance),
+        }
+    }
+}
+
+/// How two overlapping shapes touch. `normal` goes from the first shape towards the second one:
+/// moving the second one by `normal * depth`, or the first one by `-normal * depth`, separates them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CollisionManifold {
+    pub normal: Vector2<f32>,
+    pub depth: f32,
+    contacts: [Vector2<f32>; 2],
+    contact_count: usize,
+}
+
+impl CollisionManifold {
+    pub fn new(normal: Vector2<f32>, depth: f32, contacts: &[Vector2<f32>]) -> Self {
+        let mut manifold = CollisionManifold {
+            normal: normal,
+            depth: depth,
+            contacts: [Vector2::new(0.0, 0.0); 2],
+            contact_count: contacts.len().min(2),
+        };
+        for (i, contact) in contacts.iter().take(2).enumerate() {
+            manifold.contacts[i] = *contact;
+        }
+        manifold
+    }
+
+    /// The points where the shapes touch, one or two of them.
+    pub fn get_contacts(&self) -> &[Vector2<f32>] {
+        &self.contacts[..self.contact_count]
+    }
+
+    /// The same contact seen from the second shape.
+    pub fn reversed(&self) -> Self {
+        let mut manifold = *self;
+        manifold.normal = -self.normal;
+        manifold
+    }
+}
+
+/// Where a ray first meets a shape. `distance` is measured along the ray from its origin
+/// and `normal` is the one of the side that was hit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayHit {
+    pub point: Vector2<f32>,
+    pub normal: Vector2<f32>,
+    pub distance: f32,
+}
+
+impl RayHit {
+    fn new(origin: Vector2<f32>, direction: Vector2<f32>, distance: f32, normal: Vector2<f32>) -> Self {
+        RayHit {
+            point: origin + direction * distance,
+            normal: normal,
+            distance: distance,
+        }
+    }
 }
 
 bitflags!{
@@ -169,6 +513,11 @@ impl Collider {
     }
 
     pub fn collide_circle_to_rect(circle: &CircleCollider, rect: Rectangle) -> bool {
+        // Check if the rectangle contains the circle's center-point
+        if Collider::get_sector_rect_point(rect, circle.get_absolute_position()) == CENTER {
+            return true;
+        }
+
         // Check if the circle contains the rectangle's center-point
         if Collider::collide_circle_to_point(circle, Vector2::new(rect.x + rect.w as f32 / 2.0, rect.y + rect.h as f32 / 2.0)) {
             return true;
@@ -221,7 +570,8 @@ impl Collider {
     }
 
     pub fn collide_circle_to_box(circle: &CircleCollider, box_collider: &BoxCollider) -> bool {
-        return Collider::collide_circle_to_rect(circle, box_collider.as_rect());
+        // the float bounds of the box, as_rect would truncate its size
+        return Collider::collide_circle_to_box_manifold(circle, box_collider).is_some();
     }
 
     pub fn collide_box_to_box(box_collider1: &BoxCollider, box_collider2: &BoxCollider) -> bool {
@@ -230,4 +580,635 @@ impl Collider {
         box_collider1.get_absolute_bottom() > box_collider2.get_absolute_top() &&
         box_collider1.get_absolute_top() < box_collider2.get_absolute_bottom();
     }
-}
\ No newline at end of file
+
+    /// Like `collide_circle_to_circle`. Circles sharing their center are pushed apart along x.
+    pub fn collide_circle_to_circle_manifold(circle1: &CircleCollider, circle2: &CircleCollider) -> Option<CollisionManifold> {
+        let c1_pos = circle1.get_absolute_position();
+        let c2_pos = circle2.get_absolute_position();
+        let radii = circle1.get_radius() + circle2.get_radius();
+        let distance2 = c1_pos.distance2(c2_pos);
+        if distance2 >= radii * radii {
+            return None;
+        }
+        let distance = distance2.sqrt();
+        let normal = if distance > 0.0 { (c2_pos - c1_pos) / distance } else { Vector2::new(1.0, 0.0) };
+        let contact = c1_pos + normal * circle1.get_radius();
+        Some(CollisionManifold::new(normal, radii - distance, &[contact]))
+    }
+
+    /// Like `collide_circle_to_rect`. When the center of the circle is inside the rectangle
+    /// the circle is pushed out through the closest side.
+    pub fn collide_circle_to_rect_manifold(circle: &CircleCollider, rect: Rectangle) -> Option<CollisionManifold> {
+        Collider::collide_circle_to_bounds(circle, rect.get_left(), rect.get_top(), rect.get_right(), rect.get_bottom())
+    }
+
+    pub fn collide_circle_to_box_manifold(circle: &CircleCollider, box_collider: &BoxCollider) -> Option<CollisionManifold> {
+        Collider::collide_circle_to_bounds(circle, box_collider.get_absolute_left(), box_collider.get_absolute_top(),
+            box_collider.get_absolute_right(), box_collider.get_absolute_bottom())
+    }
+
+    fn collide_circle_to_bounds(circle: &CircleCollider, left: f32, top: f32, right: f32, bottom: f32) -> Option<CollisionManifold> {
+        let c_pos = circle.get_absolute_position();
+        let radius = circle.get_radius();
+        let sector = Collider::get_sector_split_rect_point(left, top, right - left, bottom - top, c_pos);
+        let closest = Vector2::new(c_pos.x.clamp(left, right), c_pos.y.clamp(top, bottom));
+        let distance2 = c_pos.distance2(closest);
+        // the center can also lie on the right or bottom side, which the sectors leave out
+        if sector == CENTER || distance2 == 0.0 {
+            // the normal goes towards the box, away from the side the circle leaves through
+            let sides = [
+                (c_pos.x - left, Vector2::new(1.0, 0.0), Vector2::new(left, c_pos.y)),
+                (right - c_pos.x, Vector2::new(-1.0, 0.0), Vector2::new(right, c_pos.y)),
+                (c_pos.y - top, Vector2::new(0.0, 1.0), Vector2::new(c_pos.x, top)),
+                (bottom - c_pos.y, Vector2::new(0.0, -1.0), Vector2::new(c_pos.x, bottom)),
+            ];
+            let mut closest = sides[0];
+            for side in &sides[1..] {
+                if side.0 < closest.0 {
+                    closest = *side;
+                }
+            }
+            return Some(CollisionManifold::new(closest.1, radius + closest.0, &[closest.2]));
+        }
+        if distance2 >= radius * radius {
+            return None;
+        }
+        let distance = distance2.sqrt();
+        Some(CollisionManifold::new((closest - c_pos) / distance, radius - distance, &[closest]))
+    }
+
+    /// Like `collide_box_to_box`. The boxes are separated along the axis where they overlap
+    /// the least, the contacts being the ends of the side of the second box inside the first one.
+    pub fn collide_box_to_box_manifold(box_collider1: &BoxCollider, box_collider2: &BoxCollider) -> Option<CollisionManifold> {
+        if !Collider::collide_box_to_box(box_collider1, box_collider2) {
+            return None;
+        }
+        let left = box_collider1.get_absolute_left().max(box_collider2.get_absolute_left());
+        let right = box_collider1.get_absolute_right().min(box_collider2.get_absolute_right());
+        let top = box_collider1.get_absolute_top().max(box_collider2.get_absolute_top());
+        let bottom = box_collider1.get_absolute_bottom().min(box_collider2.get_absolute_bottom());
+        let center1 = (box_collider1.get_absolute_left() + box_collider1.get_absolute_right()) / 2.0;
+        let center2 = (box_collider2.get_absolute_left() + box_collider2.get_absolute_right()) / 2.0;
+        let middle1 = (box_collider1.get_absolute_top() + box_collider1.get_absolute_bottom()) / 2.0;
+        let middle2 = (box_collider2.get_absolute_top() + box_collider2.get_absolute_bottom()) / 2.0;
+        if right - left < bottom - top {
+            let (normal, x) = if center2 >= center1 { (1.0, left) } else { (-1.0, right) };
+            Some(CollisionManifold::new(Vector2::new(normal, 0.0), right - left,
+                &[Vector2::new(x, top), Vector2::new(x, bottom)]))
+        } else {
+            let (normal, y) = if middle2 >= middle1 { (1.0, top) } else { (-1.0, bottom) };
+            Some(CollisionManifold::new(Vector2::new(0.0, normal), bottom - top,
+                &[Vector2::new(left, y), Vector2::new(right, y)]))
+        }
+    }
+
+    pub fn collide_polygon_to_point(polygon: &PolygonCollider, point: Vector2<f32>) -> bool {
+        Collider::separating_axis(&polygon.get_absolute_points(), &[point]).is_some()
+    }
+
+    /// The normal goes from the polygon towards the point, the shortest way out of it.
+    pub fn collide_polygon_to_point_manifold(polygon: &PolygonCollider, point: Vector2<f32>) -> Option<CollisionManifold> {
+        Collider::collide_points_manifold(&polygon.get_absolute_points(), &[point])
+    }
+
+    pub fn collide_polygon_to_line(polygon: &PolygonCollider, line_from: Vector2<f32>, line_to: Vector2<f32>) -> bool {
+        Collider::separating_axis(&polygon.get_absolute_points(), &[line_from, line_to]).is_some()
+    }
+
+    /// The normal goes from the polygon towards the line.
+    pub fn collide_polygon_to_line_manifold(polygon: &PolygonCollider, line_from: Vector2<f32>, line_to: Vector2<f32>) -> Option<CollisionManifold> {
+        Collider::collide_points_manifold(&polygon.get_absolute_points(), &[line_from, line_to])
+    }
+
+    pub fn collide_polygon_to_polygon(polygon1: &PolygonCollider, polygon2: &PolygonCollider) -> bool {
+        Collider::collide_polygon_to_polygon_manifold(polygon1, polygon2).is_some()
+    }
+
+    pub fn collide_polygon_to_polygon_manifold(polygon1: &PolygonCollider, polygon2: &PolygonCollider) -> Option<CollisionManifold> {
+        Collider::collide_points_manifold(&polygon1.get_absolute_points(), &polygon2.get_absolute_points())
+    }
+
+    pub fn collide_polygon_to_box(polygon: &PolygonCollider, box_collider: &BoxCollider) -> bool {
+        let points = polygon.get_absolute_points();
+        // trivial accept and reject with the sectors of the polygon points around the box
+        let rect = (box_collider.get_absolute_left(), box_collider.get_absolute_top(), box_collider.get_width(), box_collider.get_height());
+        let mut common = TOP_LEFT | BOTTOM_RIGHT;
+        for point in &points {
+            // strictly inside, a point on a side only touches the box
+            if point.x > rect.0 && point.x < rect.0 + rect.2 && point.y > rect.1 && point.y < rect.1 + rect.3 {
+                return true;
+            }
+            common = common & Collider::get_sector_split_rect_point(rect.0, rect.1, rect.2, rect.3, *point);
+        }
+        if common != CENTER {
+            return false;
+        }
+        Collider::separating_axis(&points, &box_collider.get_corners()).is_some()
+    }
+
+    /// The normal goes from the polygon towards the box.
+    pub fn collide_polygon_to_box_manifold(polygon: &PolygonCollider, box_collider: &BoxCollider) -> Option<CollisionManifold> {
+        Collider::collide_points_manifold(&polygon.get_absolute_points(), &box_collider.get_corners())
+    }
+
+    pub fn collide_polygon_to_circle(polygon: &PolygonCollider, circle: &CircleCollider) -> bool {
+        Collider::collide_polygon_to_circle_manifold(polygon, circle).is_some()
+    }
+
+    /// The normal goes from the polygon towards the circle.
+    pub fn collide_polygon_to_circle_manifold(polygon: &PolygonCollider, circle: &CircleCollider) -> Option<CollisionManifold> {
+        let points = polygon.get_absolute_points();
+        let center = circle.get_absolute_position();
+        let radius = circle.get_radius();
+        let closest = points.iter().fold(points[0], |closest, point| {
+            if point.distance2(center) < closest.distance2(center) { *point } else { closest }
+        });
+        let mut axes = edge_normals(&points);
+        if closest != center {
+            axes.push((center - closest).normalize());
+        }
+        let mut best: Option<(Vector2<f32>, f32)> = None;
+        for axis in axes {
+            let (min, max) = project(&points, axis);
+            let c = center.dot(axis);
+            let overlap = (max - (c - radius)).min((c + radius) - min);
+            if overlap <= 0.0 {
+                return None;
+            }
+            if best.map_or(true, |(_, depth)| overlap < depth) {
+                best = Some((axis, overlap));
+            }
+        }
+        let (mut normal, depth) = best?;
+        if (center - centroid(&points)).dot(normal) < 0.0 {
+            normal = -normal;
+        }
+        Some(CollisionManifold::new(normal, depth, &[center - normal * radius]))
+    }
+
+    /// Separating axis test between two convex outlines. Returns the axis of least
+    /// penetration, going from the first outline towards the second one, and the penetration.
+    fn separating_axis(points1: &[Vector2<f32>], points2: &[Vector2<f32>]) -> Option<(Vector2<f32>, f32)> {
+        let mut axes = edge_normals(points1);
+        axes.extend(edge_normals(points2));
+        let mut best: Option<(Vector2<f32>, f32)> = None;
+        for axis in axes {
+            let (min1, max1) = project(points1, axis);
+            let (min2, max2) = project(points2, axis);
+            let overlap = (max1 - min2).min(max2 - min1);
+            if overlap <= 0.0 {
+                return None;
+            }
+            if best.map_or(true, |(_, depth)| overlap < depth) {
+                best = Some((axis, overlap));
+            }
+        }
+        let (mut normal, depth) = best?;
+        if (centroid(points2) - centroid(points1)).dot(normal) < 0.0 {
+            normal = -normal;
+        }
+        Some((normal, depth))
+    }
+
+    /// SAT manifold between two convex outlines. The contacts are the points of the
+    /// second outline reaching the deepest into the first one, or the point of the first one
+    /// reaching into a side of the second one. When two sides touch, the part they share.
+    fn collide_points_manifold(points1: &[Vector2<f32>], points2: &[Vector2<f32>]) -> Option<CollisionManifold> {
+        let (normal, depth) = Collider::separating_axis(points1, points2)?;
+        let support1 = support_points(points1, normal);
+        let support2 = support_points(points2, -normal);
+        let contacts = match (support1.len(), support2.len()) {
+            (_, 1) => support2,
+            (1, _) => support1,
+            _ => {
+                // both sides are along the same direction, keep the part they share
+                let tangent = Vector2::new(-normal.y, normal.x);
+                let mut ends: Vec<f32> = support1.iter().chain(support2.iter()).map(|p| p.dot(tangent)).collect();
+                ends.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let base = support2[0];
+                let offset = base.dot(tangent);
+                vec![base + tangent * (ends[1] - offset), base + tangent * (ends[2] - offset)]
+            }
+        };
+        Some(CollisionManifold::new(normal, depth, &contacts))
+    }
+
+    /// Where the ray going from `origin` along `direction` first enters the box, if it does
+    /// within `max_distance`. A ray starting inside the box hits it right away, the normal
+    /// facing the ray.
+    pub fn raycast_box(box_collider: &BoxCollider, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+        Collider::raycast_bounds(box_collider.get_absolute_left(), box_collider.get_absolute_top(),
+            box_collider.get_absolute_right(), box_collider.get_absolute_bottom(), origin, direction, max_distance)
+    }
+
+    pub fn raycast_rect(rect: Rectangle, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+        Collider::raycast_bounds(rect.get_left(), rect.get_top(), rect.get_right(), rect.get_bottom(), origin, direction, max_distance)
+    }
+
+    fn raycast_bounds(left: f32, top: f32, right: f32, bottom: f32, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+        let direction = ray_direction(direction)?;
+        // slabs: the ray is inside the box between the last entry and the first exit
+        let mut enter = 0.0;
+        let mut exit = max_distance;
+        let mut normal = -direction;
+        let slabs = [
+            (origin.x, direction.x, left, right, Vector2::new(-1.0, 0.0)),
+            (origin.y, direction.y, top, bottom, Vector2::new(0.0, -1.0)),
+        ];
+        for &(start, delta, min, max, min_normal) in &slabs {
+            if delta == 0.0 {
+                if start < min || start > max {
+                    return None;
+                }
+                continue;
+            }
+            let (near, far, near_normal) = if delta > 0.0 {
+                ((min - start) / delta, (max - start) / delta, min_normal)
+            } else {
+                ((max - start) / delta, (min - start) / delta, -min_normal)
+            };
+            if near > enter {
+                enter = near;
+                normal = near_normal;
+            }
+            exit = exit.min(far);
+            if enter > exit {
+                return None;
+            }
+        }
+        Some(RayHit::new(origin, direction, enter, normal))
+    }
+
+    pub fn raycast_circle(circle: &CircleCollider, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+        let direction = ray_direction(direction)?;
+        let center = circle.get_absolute_position();
+        let radius = circle.get_radius();
+        let to_origin = origin - center;
+        let c = to_origin.magnitude2() - radius * radius;
+        if c <= 0.0 {
+            return Some(RayHit::new(origin, direction, 0.0, -direction));
+        }
+        let b = to_origin.dot(direction);
+        let discriminant = b * b - c;
+        if b > 0.0 || discriminant < 0.0 {
+            return None;
+        }
+        let distance = -b - discriminant.sqrt();
+        if distance > max_distance {
+            return None;
+        }
+        let point = origin + direction * distance;
+        Some(RayHit::new(origin, direction, distance, (point - center).normalize()))
+    }
+
+    pub fn raycast_polygon(polygon: &PolygonCollider, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+        let direction = ray_direction(direction)?;
+        let points = polygon.get_absolute_points();
+        let center = centroid(&points);
+        let count = points.len();
+        let mut enter = 0.0;
+        let mut exit = max_distance;
+        let mut normal = -direction;
+        for i in 0..count {
+            let a = points[i];
+            let edge = points[(i + 1) % count] - a;
+            if edge.magnitude2() == 0.0 {
+                continue;
+            }
+            let mut outward = Vector2::new(-edge.y, edge.x).normalize();
+            if (a - center).dot(outward) < 0.0 {
+                outward = -outward;
+            }
+            // the ray is on the inner side of the edge where dot((p - a), outward) <= 0
+            let distance = (a - origin).dot(outward);
+            let speed = direction.dot(outward);
+            if speed == 0.0 {
+                if distance < 0.0 {
+                    return None;
+                }
+                continue;
+            }
+            let t = distance / speed;
+            if speed < 0.0 {
+                if t > enter {
+                    enter = t;
+                    normal = outward;
+                }
+            } else {
+                exit = exit.min(t);
+            }
+            if enter > exit {
+                return None;
+            }
+        }
+        Some(RayHit::new(origin, direction, enter, normal))
+    }
+}
+
+fn ray_direction(direction: Vector2<f32>) -> Option<Vector2<f32>> {
+    if direction.magnitude2() == 0.0 {
+        return None;
+    }
+    Some(direction.normalize())
+}
+
+/// Unit normals of the sides of the outline. A segment has a single side.
+fn edge_normals(points: &[Vector2<f32>]) -> Vec<Vector2<f32>> {
+    let mut normals = Vec::new();
+    let count = points.len();
+    if count < 2 {
+        return normals;
+    }
+    let sides = if count == 2 { 1 } else { count };
+    for i in 0..sides {
+        let edge = points[(i + 1) % count] - points[i];
+        if edge.magnitude2() > 0.0 {
+            normals.push(Vector2::new(-edge.y, edge.x).normalize());
+        }
+    }
+    normals
+}
+
+fn project(points: &[Vector2<f32>], axis: Vector2<f32>) -> (f32, f32) {
+    let mut min = ::std::f32::INFINITY;
+    let mut max = ::std::f32::NEG_INFINITY;
+    for point in points {
+        let p = point.dot(axis);
+        min = min.min(p);
+        max = max.max(p);
+    }
+    (min, max)
+}
+
+/// Area of the outline, positive if its points go clockwise on screen (y pointing down).
+fn signed_area(points: &[Vector2<f32>]) -> f32 {
+    let count = points.len();
+    let mut area = 0.0;
+    for i in 0..count {
+        let a = points[i];
+        let b = points[(i + 1) % count];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area / 2.0
+}
+
+fn centroid(points: &[Vector2<f32>]) -> Vector2<f32> {
+    let sum = points.iter().fold(Vector2::new(0.0, 0.0), |sum, point| sum + *point);
+    sum / points.len() as f32
+}
+
+/// The points furthest along the direction, two of them if a side faces it.
+fn support_points(points: &[Vector2<f32>], direction: Vector2<f32>) -> Vec<Vector2<f32>> {
+    let (_, max) = project(points, direction);
+    let mut support: Vec<Vector2<f32>> = points.iter().cloned()
+        .filter(|point| max - point.dot(direction) < 1e-4)
+        .collect();
+    support.truncate(2);
+    support
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circle(x: f32, y: f32, radius: f32) -> CircleCollider {
+        CircleCollider::new(radius, Vector2::new(x, y))
+    }
+
+    fn box_at(x: f32, y: f32, width: f32, height: f32) -> BoxCollider {
+        BoxCollider::new(Vector2::new(x, y), width, height)
+    }
+
+    fn assert_manifold(manifold: Option<CollisionManifold>, normal: (f32, f32), depth: f32, contacts: &[(f32, f32)]) {
+        let manifold = manifold.expect("the shapes should overlap");
+        assert_eq!(manifold.normal, Vector2::new(normal.0, normal.1));
+        assert!((manifold.depth - depth).abs() < 1e-5, "depth {} instead of {}", manifold.depth, depth);
+        let expected: Vec<Vector2<f32>> = contacts.iter().map(|c| Vector2::new(c.0, c.1)).collect();
+        assert_eq!(manifold.get_contacts(), &expected[..]);
+    }
+
+    #[test]
+    fn circle_inside_box_leaves_through_the_closest_side() {
+        let b = box_at(0.0, 0.0, 10.0, 20.0);
+        assert_manifold(Collider::collide_circle_to_box_manifold(&circle(2.0, 10.0, 1.0), &b), (1.0, 0.0), 3.0, &[(0.0, 10.0)]);
+        assert_manifold(Collider::collide_circle_to_box_manifold(&circle(7.0, 10.0, 1.0), &b), (-1.0, 0.0), 4.0, &[(10.0, 10.0)]);
+        assert_manifold(Collider::collide_circle_to_box_manifold(&circle(5.0, 1.0, 1.0), &b), (0.0, 1.0), 2.0, &[(5.0, 0.0)]);
+        assert_manifold(Collider::collide_circle_to_box_manifold(&circle(5.0, 18.5, 1.0), &b), (0.0, -1.0), 2.5, &[(5.0, 20.0)]);
+    }
+
+    #[test]
+    fn circle_centered_on_the_right_or_bottom_side() {
+        let b = box_at(0.0, 0.0, 10.0, 20.0);
+        assert_manifold(Collider::collide_circle_to_box_manifold(&circle(10.0, 10.0, 2.0), &b), (-1.0, 0.0), 2.0, &[(10.0, 10.0)]);
+        assert_manifold(Collider::collide_circle_to_box_manifold(&circle(5.0, 20.0, 2.0), &b), (0.0, -1.0), 2.0, &[(5.0, 20.0)]);
+        assert!(Collider::collide_circle_to_box(&circle(10.0, 10.0, 2.0), &b));
+    }
+
+    #[test]
+    fn circle_outside_box() {
+        let b = box_at(0.0, 0.0, 10.0, 20.0);
+        assert_manifold(Collider::collide_circle_to_box_manifold(&circle(12.0, 10.0, 3.0), &b), (-1.0, 0.0), 1.0, &[(10.0, 10.0)]);
+        assert_manifold(Collider::collide_circle_to_box_manifold(&circle(5.0, -2.0, 3.0), &b), (0.0, 1.0), 1.0, &[(5.0, 0.0)]);
+    }
+
+    #[test]
+    fn touching_shapes_dont_collide() {
+        let b = box_at(0.0, 0.0, 10.0, 20.0);
+        assert!(Collider::collide_circle_to_box_manifold(&circle(13.0, 10.0, 3.0), &b).is_none());
+        assert!(Collider::collide_circle_to_box_manifold(&circle(5.0, -3.0, 3.0), &b).is_none());
+        assert!(!Collider::collide_circle_to_box(&circle(13.0, 10.0, 3.0), &b));
+        assert!(Collider::collide_circle_to_circle_manifold(&circle(0.0, 0.0, 2.0), &circle(5.0, 0.0, 3.0)).is_none());
+        assert!(!Collider::collide_circle_to_circle(&circle(0.0, 0.0, 2.0), &circle(5.0, 0.0, 3.0)));
+        assert!(Collider::collide_box_to_box_manifold(&b, &box_at(10.0, 0.0, 5.0, 5.0)).is_none());
+        assert!(Collider::collide_box_to_box_manifold(&b, &box_at(0.0, 20.0, 5.0, 5.0)).is_none());
+    }
+
+    #[test]
+    fn circle_to_box_uses_the_float_size() {
+        let b = box_at(0.0, 0.0, 10.5, 10.0);
+        let c = circle(10.8, 5.0, 0.5);
+        assert!(Collider::collide_circle_to_box_manifold(&c, &b).is_some());
+        assert!(Collider::collide_circle_to_box(&c, &b));
+    }
+
+    #[test]
+    fn circles() {
+        assert_manifold(Collider::collide_circle_to_circle_manifold(&circle(0.0, 0.0, 2.0), &circle(4.0, 0.0, 3.0)),
+            (1.0, 0.0), 1.0, &[(2.0, 0.0)]);
+        assert_manifold(Collider::collide_circle_to_circle_manifold(&circle(0.0, 4.0, 2.0), &circle(0.0, 0.0, 3.0)),
+            (0.0, -1.0), 1.0, &[(0.0, 2.0)]);
+    }
+
+    #[test]
+    fn concentric_circles_are_pushed_apart_along_x() {
+        assert_manifold(Collider::collide_circle_to_circle_manifold(&circle(1.0, 1.0, 2.0), &circle(1.0, 1.0, 3.0)),
+            (1.0, 0.0), 5.0, &[(3.0, 1.0)]);
+    }
+
+    #[test]
+    fn boxes() {
+        let b = box_at(0.0, 0.0, 10.0, 10.0);
+        assert_manifold(Collider::collide_box_to_box_manifold(&b, &box_at(8.0, 2.0, 10.0, 4.0)),
+            (1.0, 0.0), 2.0, &[(8.0, 2.0), (8.0, 6.0)]);
+        assert_manifold(Collider::collide_box_to_box_manifold(&b, &box_at(2.0, -7.0, 4.0, 10.0)),
+            (0.0, -1.0), 3.0, &[(2.0, 3.0), (6.0, 3.0)]);
+    }
+
+    fn polygon(points: &[(f32, f32)]) -> PolygonCollider {
+        PolygonCollider::new(Vector2::new(0.0, 0.0), points.iter().map(|p| Vector2::new(p.0, p.1)).collect()).unwrap()
+    }
+
+    fn assert_near(point: Vector2<f32>, x: f32, y: f32) {
+        assert!((point.x - x).abs() < 1e-4 && (point.y - y).abs() < 1e-4, "{:?} instead of ({}, {})", point, x, y);
+    }
+
+    #[test]
+    fn degenerate_polygons_are_refused() {
+        let new = |points: &[(f32, f32)]| PolygonCollider::new(Vector2::new(0.0, 0.0), points.iter().map(|p| Vector2::new(p.0, p.1)).collect());
+        assert!(new(&[(0.0, 0.0), (10.0, 0.0)]).is_none());
+        assert!(new(&[(0.0, 0.0), (5.0, 5.0), (10.0, 10.0)]).is_none());
+        assert!(new(&[(0.0, 0.0), (10.0, 0.0), (10.0, 0.0), (0.0, 10.0)]).is_none());
+        assert!(new(&[(0.0, 10.0), (10.0, 0.0), (0.0, 10.0)]).is_none());
+        assert!(new(&[(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)]).is_some());
+    }
+
+    #[test]
+    fn separating_axis_picks_the_least_penetration() {
+        let square = [Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0), Vector2::new(10.0, 10.0), Vector2::new(0.0, 10.0)];
+        let other: Vec<Vector2<f32>> = square.iter().map(|p| p + Vector2::new(8.0, 3.0)).collect();
+        assert_eq!(Collider::separating_axis(&square, &other), Some((Vector2::new(1.0, 0.0), 2.0)));
+        assert_eq!(Collider::separating_axis(&other, &square), Some((Vector2::new(-1.0, 0.0), 2.0)));
+        let apart: Vec<Vector2<f32>> = square.iter().map(|p| p + Vector2::new(10.0, 0.0)).collect();
+        assert!(Collider::separating_axis(&square, &apart).is_none());
+    }
+
+    #[test]
+    fn rotated_boxes_against_boxes() {
+        let diamond = PolygonCollider::oriented_box(Vector2::new(0.0, 0.0), 10.0, 10.0, ::std::f32::consts::PI / 4.0);
+        let square = PolygonCollider::oriented_box(Vector2::new(0.0, 0.0), 10.0, 10.0, 0.0);
+        // beyond the side of the square but reached by the corner of the diamond
+        let beside = box_at(6.0, -1.0, 4.0, 2.0);
+        // in the corner of the square, cut off by the diamond
+        let corner = box_at(4.0, 4.0, 2.0, 2.0);
+        assert!(Collider::collide_polygon_to_box(&diamond, &beside));
+        assert!(!Collider::collide_polygon_to_box(&square, &beside));
+        assert!(!Collider::collide_polygon_to_box(&diamond, &corner));
+        assert!(Collider::collide_polygon_to_box(&square, &corner));
+
+        let manifold = Collider::collide_polygon_to_box_manifold(&diamond, &beside).unwrap();
+        assert_near(manifold.normal, 1.0, 0.0);
+        assert!((manifold.depth - (50.0f32.sqrt() - 6.0)).abs() < 1e-4);
+        assert_eq!(manifold.get_contacts().len(), 1);
+        assert_near(manifold.get_contacts()[0], 50.0f32.sqrt(), 0.0);
+    }
+
+    #[test]
+    fn oriented_boxes_turn_around_their_origin_then_the_entity() {
+        let mut b = PolygonCollider::oriented_box(Vector2::new(10.0, 0.0), 4.0, 2.0, ::std::f32::consts::PI / 2.0);
+        let points = b.get_absolute_points();
+        assert_near(points[0], 11.0, -2.0);
+        assert_near(points[2], 9.0, 2.0);
+        b.set_entity_position(Vector2::new(5.0, 5.0));
+        b.set_entity_rotation(::std::f32::consts::PI / 2.0);
+        assert!((b.get_absolute_left() - 3.0).abs() < 1e-4 && (b.get_absolute_right() - 7.0).abs() < 1e-4);
+        assert!((b.get_absolute_top() - 14.0).abs() < 1e-4 && (b.get_absolute_bottom() - 16.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn touching_polygons_dont_collide() {
+        let triangle = polygon(&[(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)]);
+        assert!(!Collider::collide_polygon_to_box(&triangle, &box_at(0.0, -5.0, 10.0, 5.0)));
+        assert!(Collider::collide_polygon_to_box_manifold(&triangle, &box_at(0.0, -5.0, 10.0, 5.0)).is_none());
+        let other = polygon(&[(10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        assert!(!Collider::collide_polygon_to_polygon(&triangle, &other));
+        assert!(!Collider::collide_polygon_to_circle(&triangle, &circle(-3.0, 5.0, 3.0)));
+    }
+
+    #[test]
+    fn circles_near_a_vertex_are_tested_against_the_vertex() {
+        let triangle = polygon(&[(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)]);
+        // overlaps the triangle along both axes, but is further than its radius from the corner
+        assert!(!Collider::collide_polygon_to_circle(&triangle, &circle(-2.0, -2.0, 2.5)));
+        let manifold = Collider::collide_polygon_to_circle_manifold(&triangle, &circle(-2.0, -2.0, 3.0)).unwrap();
+        let diagonal = 0.5f32.sqrt();
+        assert_near(manifold.normal, -diagonal, -diagonal);
+        assert!((manifold.depth - (3.0 - 8.0f32.sqrt())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn polygon_manifolds_go_from_the_first_shape_to_the_second() {
+        let square = polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        let right = polygon(&[(8.0, 2.0), (18.0, 2.0), (18.0, 6.0), (8.0, 6.0)]);
+        assert_manifold(Collider::collide_polygon_to_polygon_manifold(&square, &right), (1.0, 0.0), 2.0, &[(8.0, 2.0), (8.0, 6.0)]);
+        assert_manifold(Collider::collide_polygon_to_polygon_manifold(&right, &square), (-1.0, 0.0), 2.0, &[(10.0, 6.0), (10.0, 2.0)]);
+        assert_manifold(Collider::collide_polygon_to_circle_manifold(&square, &circle(5.0, 12.0, 3.0)), (0.0, 1.0), 1.0, &[(5.0, 9.0)]);
+
+        assert_manifold(Collider::collide_polygon_to_point_manifold(&square, Vector2::new(9.0, 4.0)), (1.0, 0.0), 1.0, &[(9.0, 4.0)]);
+        assert!(Collider::collide_polygon_to_point_manifold(&square, Vector2::new(11.0, 4.0)).is_none());
+        assert!(Collider::collide_polygon_to_point(&square, Vector2::new(9.0, 4.0)));
+        let manifold = Collider::collide_polygon_to_line_manifold(&square, Vector2::new(3.0, -2.0), Vector2::new(3.0, 1.0)).unwrap();
+        assert_eq!((manifold.normal, manifold.depth), (Vector2::new(0.0, -1.0), 1.0));
+        assert!(Collider::collide_polygon_to_line_manifold(&square, Vector2::new(3.0, -2.0), Vector2::new(3.0, -1.0)).is_none());
+    }
+
+    #[test]
+    fn tiled_polygons_turn_clockwise_around_the_object() {
+        let points = vec![Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0), Vector2::new(0.0, 10.0)];
+        let polygon = PolygonCollider::from_tiled_polygon(100.0, 50.0, 90.0, points).unwrap();
+        let points = polygon.get_absolute_points();
+        assert_near(points[0], 100.0, 50.0);
+        assert_near(points[1], 100.0, 60.0);
+        assert_near(points[2], 90.0, 50.0);
+        // concave polygons are kept, with a warning
+        let concave = vec![Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0), Vector2::new(2.0, 2.0), Vector2::new(0.0, 10.0)];
+        assert!(!PolygonCollider::from_tiled_polygon(0.0, 0.0, 0.0, concave).unwrap().is_convex());
+        assert!(PolygonCollider::from_tiled_polygon(0.0, 0.0, 0.0, vec![Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0)]).is_none());
+    }
+
+    fn assert_hit(hit: Option<RayHit>, point: (f32, f32), normal: (f32, f32), distance: f32) {
+        let hit = hit.expect("the ray should hit the shape");
+        assert_near(hit.point, point.0, point.1);
+        assert_near(hit.normal, normal.0, normal.1);
+        assert!((hit.distance - distance).abs() < 1e-4, "distance {} instead of {}", hit.distance, distance);
+    }
+
+    #[test]
+    fn rays_against_boxes() {
+        let b = box_at(10.0, -5.0, 10.0, 10.0);
+        let right = Vector2::new(1.0, 0.0);
+        assert_hit(Collider::raycast_box(&b, Vector2::new(0.0, 0.0), right, 100.0), (10.0, 0.0), (-1.0, 0.0), 10.0);
+        assert_hit(Collider::raycast_box(&b, Vector2::new(15.0, -20.0), Vector2::new(0.0, 3.0), 100.0), (15.0, -5.0), (0.0, -1.0), 15.0);
+        assert_hit(Collider::raycast_box(&b, Vector2::new(15.0, 0.0), right, 100.0), (15.0, 0.0), (-1.0, 0.0), 0.0);
+        assert!(Collider::raycast_box(&b, Vector2::new(0.0, 0.0), right, 9.0).is_none());
+        assert!(Collider::raycast_box(&b, Vector2::new(0.0, 0.0), -right, 100.0).is_none());
+        assert!(Collider::raycast_box(&b, Vector2::new(0.0, 20.0), right, 100.0).is_none());
+        assert!(Collider::raycast_box(&b, Vector2::new(0.0, 0.0), Vector2::new(0.0, 0.0), 100.0).is_none());
+        assert!(Collider::raycast_box(&b, Vector2::new(0.0, 0.0), right, ::std::f32::INFINITY).is_some());
+    }
+
+    #[test]
+    fn rays_against_circles() {
+        let c = circle(20.0, 0.0, 5.0);
+        assert_hit(Collider::raycast_circle(&c, Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), 100.0), (15.0, 0.0), (-1.0, 0.0), 15.0);
+        assert_hit(Collider::raycast_circle(&c, Vector2::new(20.0, -20.0), Vector2::new(0.0, 1.0), 100.0), (20.0, -5.0), (0.0, -1.0), 15.0);
+        assert_hit(Collider::raycast_circle(&c, Vector2::new(22.0, 0.0), Vector2::new(0.0, 1.0), 100.0), (22.0, 0.0), (0.0, -1.0), 0.0);
+        assert!(Collider::raycast_circle(&c, Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), 14.0).is_none());
+        assert!(Collider::raycast_circle(&c, Vector2::new(0.0, 0.0), Vector2::new(-1.0, 0.0), 100.0).is_none());
+        assert!(Collider::raycast_circle(&c, Vector2::new(0.0, 6.0), Vector2::new(1.0, 0.0), 100.0).is_none());
+    }
+
+    #[test]
+    fn rays_against_polygons() {
+        let diamond = polygon(&[(10.0, 0.0), (20.0, -10.0), (30.0, 0.0), (20.0, 10.0)]);
+        let diagonal = 0.5f32.sqrt();
+        assert_hit(Collider::raycast_polygon(&diamond, Vector2::new(0.0, 5.0), Vector2::new(1.0, 0.0), 100.0),
+            (15.0, 5.0), (-diagonal, diagonal), 15.0);
+        assert_hit(Collider::raycast_polygon(&diamond, Vector2::new(25.0, -30.0), Vector2::new(0.0, 1.0), 100.0),
+            (25.0, -5.0), (diagonal, -diagonal), 25.0);
+        assert_hit(Collider::raycast_polygon(&diamond, Vector2::new(20.0, 0.0), Vector2::new(1.0, 0.0), 100.0),
+            (20.0, 0.0), (-1.0, 0.0), 0.0);
+        assert!(Collider::raycast_polygon(&diamond, Vector2::new(0.0, 5.0), Vector2::new(1.0, 0.0), 14.0).is_none());
+        assert!(Collider::raycast_polygon(&diamond, Vector2::new(0.0, 11.0), Vector2::new(1.0, 0.0), 100.0).is_none());
+        assert!(Collider::raycast_polygon(&diamond, Vector2::new(0.0, 5.0), Vector2::new(-1.0, 0.0), 100.0).is_none());
+    }
+}
@@ -0,0 +1,117 @@
+extern crate cgmath;
+
+use collider::{ColliderShape, CollisionManifold, RayHit};
+use colliderlist::ColliderList;
+use component::Component;
+use rectangle::Rectangle;
+use storage::DenseVecStorage;
+use self::cgmath::Vector2;
+
+/// Shapes of an entity, placed relative to its world position, along with the collision
+/// layer it is on and the layers it collides with. The Scene keeps the shapes on the entity
+/// and in the spatial hash as it moves.
+#[derive(Debug, Clone)]
+pub struct ColliderComponent {
+    colliders: ColliderList,
+    layer: i32,
+    mask: i32,
+}
+
+impl Component for ColliderComponent {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl ColliderComponent {
+    /// A collider on layer 1 colliding with every layer.
+    pub fn new(shape: ColliderShape) -> Self {
+        ColliderComponent::from_list(ColliderList::new().with(shape))
+    }
+
+    pub fn from_list(colliders: ColliderList) -> Self {
+        ColliderComponent {
+            colliders: colliders,
+            layer: 1,
+            mask: -1,
+        }
+    }
+
+    pub fn with_shape(mut self, shape: ColliderShape) -> Self {
+        self.colliders.add(shape);
+        self
+    }
+
+    pub fn with_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: i32) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    pub fn get_colliders(&self) -> &ColliderList {
+        return &self.colliders;
+    }
+
+    pub fn get_colliders_mut(&mut self) -> &mut ColliderList {
+        return &mut self.colliders;
+    }
+
+    /// Bits of the layers the collider is on.
+    pub fn get_layer(&self) -> i32 {
+        return self.layer;
+    }
+
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+
+    /// Bits of the layers the collider collides with.
+    pub fn get_mask(&self) -> i32 {
+        return self.mask;
+    }
+
+    pub fn set_mask(&mut self, mask: i32) {
+        self.mask = mask;
+    }
+
+    pub fn get_entity_position(&self) -> Vector2<f32> {
+        return self.colliders.get_entity_position();
+    }
+
+    pub fn set_entity_position(&mut self, position: Vector2<f32>) {
+        self.colliders.set_entity_position(position);
+    }
+
+    pub fn get_entity_rotation(&self) -> f32 {
+        return self.colliders.get_entity_rotation();
+    }
+
+    pub fn set_entity_rotation(&mut self, rotation: f32) {
+        self.colliders.set_entity_rotation(rotation);
+    }
+
+    pub fn get_bounds(&self) -> Option<Rectangle> {
+        return self.colliders.get_bounds();
+    }
+
+    /// Whether the shapes overlap, the layers and masks being ignored.
+    pub fn collide(&self, other: &ColliderComponent) -> bool {
+        return self.colliders.collide(&other.colliders);
+    }
+
+    /// See `ColliderList::collide_manifolds`.
+    pub fn collide_manifolds(&self, other: &ColliderComponent) -> Vec<CollisionManifold> {
+        return self.colliders.collide_manifolds(&other.colliders);
+    }
+
+    pub fn raycast(&self, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+        return self.colliders.raycast(origin, direction, max_distance);
+    }
+
+    /// Whether each collider is on a layer the other one collides with.
+    pub fn can_collide_with(&self, other: &ColliderComponent) -> bool {
+        return self.mask & other.layer != 0 && other.mask & self.layer != 0;
+    }
+}
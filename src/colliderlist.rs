@@ -1,11 +1,164 @@
-pub struct ColliderList {
+extern crate cgmath;
+
+use collider::{ColliderShape, CollisionManifold, RayHit};
+use rectangle::Rectangle;
+use self::cgmath::Vector2;
+use std::vec::Vec;
 
+/// Compound collider made of several shapes moving together. It collides with
+/// anything one of its shapes collides with.
+#[derive(Debug, Clone)]
+pub struct ColliderList {
+    colliders: Vec<ColliderShape>,
+    position: Vector2<f32>,
+    rotation: f32,
 }
 
 impl ColliderList {
     pub fn new() -> Self {
         ColliderList {
-            
+            colliders: Vec::new(),
+            position: Vector2::new(0.0, 0.0),
+            rotation: 0.0,
         }
     }
-}
\ No newline at end of file
+
+    pub fn with(mut self, collider: ColliderShape) -> Self {
+        self.add(collider);
+        self
+    }
+
+    pub fn add(&mut self, mut collider: ColliderShape) {
+        collider.set_entity_position(self.position);
+        collider.set_entity_rotation(self.rotation);
+        self.colliders.push(collider);
+    }
+
+    pub fn get_colliders(&self) -> &[ColliderShape] {
+        &self.colliders
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colliders.is_empty()
+    }
+
+    pub fn get_entity_position(&self) -> Vector2<f32> {
+        self.position
+    }
+
+    pub fn set_entity_position(&mut self, position: Vector2<f32>) {
+        self.position = position;
+        for collider in &mut self.colliders {
+            collider.set_entity_position(position);
+        }
+    }
+
+    pub fn get_entity_rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn set_entity_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+        for collider in &mut self.colliders {
+            collider.set_entity_rotation(rotation);
+        }
+    }
+
+    /// Smallest rectangle around every shape, rounded outwards. None if the list is empty.
+    pub fn get_bounds(&self) -> Option<Rectangle> {
+        let first = self.colliders.first()?;
+        let mut left = first.get_absolute_left();
+        let mut right = first.get_absolute_right();
+        let mut top = first.get_absolute_top();
+        let mut bottom = first.get_absolute_bottom();
+        for collider in &self.colliders[1..] {
+            left = left.min(collider.get_absolute_left());
+            right = right.max(collider.get_absolute_right());
+            top = top.min(collider.get_absolute_top());
+            bottom = bottom.max(collider.get_absolute_bottom());
+        }
+        Some(Rectangle::new(left, top, (right - left).ceil() as i32, (bottom - top).ceil() as i32))
+    }
+
+    pub fn collide_shape(&self, shape: &ColliderShape) -> bool {
+        self.colliders.iter().any(|collider| collider.collide(shape))
+    }
+
+    pub fn collide(&self, other: &ColliderList) -> bool {
+        other.colliders.iter().any(|shape| self.collide_shape(shape))
+    }
+
+    /// The closest shape hit by the ray, see `Collider::raycast_box`.
+    pub fn raycast(&self, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+        for collider in &self.colliders {
+            match collider.raycast(origin, direction, max_distance) {
+                Some(hit) => {
+                    if closest.map_or(true, |closest| hit.distance < closest.distance) {
+                        closest = Some(hit);
+                    }
+                },
+                None => {}
+            }
+        }
+        closest
+    }
+
+    /// One manifold for each pair of overlapping shapes, the normals going towards `other`.
+    pub fn collide_manifolds(&self, other: &ColliderList) -> Vec<CollisionManifold> {
+        let mut manifolds = Vec::new();
+        for collider in &self.colliders {
+            for shape in &other.colliders {
+                match collider.collide_manifold(shape) {
+                    Some(manifold) => manifolds.push(manifold),
+                    None => {}
+                }
+            }
+        }
+        manifolds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use collider::{BoxCollider, CircleCollider, ColliderShape};
+    use colliderlist::ColliderList;
+    use super::cgmath::Vector2;
+
+    fn circle(radius: f32, x: f32, y: f32) -> ColliderShape {
+        ColliderShape::Circle(CircleCollider::new(radius, Vector2::new(x, y)))
+    }
+
+    #[test]
+    fn compound_lists_collide_through_any_of_their_shapes() {
+        let mut list = ColliderList::new()
+            .with(ColliderShape::Box(BoxCollider::new(Vector2::new(0.0, 0.0), 4.0, 4.0)))
+            .with(circle(3.0, 20.0, 0.0));
+        list.set_entity_position(Vector2::new(100.0, 0.0));
+        let bounds = list.get_bounds().unwrap();
+        assert_eq!((bounds.x, bounds.y, bounds.w, bounds.h), (100.0, -3.0, 23, 7));
+
+        let mut other = ColliderList::new().with(circle(2.0, 0.0, 0.0));
+        other.set_entity_position(Vector2::new(124.0, 0.0));
+        assert!(list.collide(&other) && other.collide(&list));
+        let manifolds = list.collide_manifolds(&other);
+        assert_eq!(manifolds.len(), 1);
+        assert!(manifolds[0].normal.x > 0.99);
+
+        // between the box and the circle
+        other.set_entity_position(Vector2::new(110.0, 0.0));
+        assert!(!list.collide(&other));
+        assert!(list.collide_manifolds(&other).is_empty());
+    }
+
+    #[test]
+    fn shapes_added_later_follow_the_entity() {
+        let mut list = ColliderList::new();
+        assert!(list.get_bounds().is_none());
+        list.set_entity_position(Vector2::new(50.0, 50.0));
+        list.add(circle(1.0, 0.0, 0.0));
+        let bounds = list.get_bounds().unwrap();
+        assert_eq!((bounds.x, bounds.y), (49.0, 49.0));
+        assert!(!list.collide(&ColliderList::new()));
+    }
+}
@@ -24,4 +24,38 @@ impl Color {
             a: 1.0,
         }
     }
+
+    pub fn black() -> Color {
+        Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+            a: 1.0,
+        }
+    }
+
+    pub fn with_rgba(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color {
+            r: r,
+            g: g,
+            b: b,
+            a: a,
+        }
+    }
+
+    pub fn get_r(&self) -> f32 {
+        self.r
+    }
+
+    pub fn get_g(&self) -> f32 {
+        self.g
+    }
+
+    pub fn get_b(&self) -> f32 {
+        self.b
+    }
+
+    pub fn get_a(&self) -> f32 {
+        self.a
+    }
 }
\ No newline at end of file
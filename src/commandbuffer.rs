@@ -0,0 +1,127 @@
+use component::Component;
+use entity::Entity;
+use world::World;
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::mem;
+use std::vec::Vec;
+
+pub type Command<T> = Box<dyn FnOnce(&mut World<T>)>;
+
+/// Components for an entity that will be spawned by a CommandBuffer.
+pub struct EntityBuilder<T> {
+    components: Vec<Box<dyn FnOnce(&mut World<T>, Entity)>>,
+}
+
+impl<T: 'static> EntityBuilder<T> {
+    pub fn new() -> Self {
+        EntityBuilder {
+            components: Vec::new(),
+        }
+    }
+
+    pub fn with<C: Component>(mut self, component: C) -> Self {
+        self.components.push(Box::new(move |world: &mut World<T>, entity: Entity| {
+            world.add_component_to_entity(entity, component);
+        }));
+        self
+    }
+
+    pub fn with_tag(mut self, tag: u32) -> Self {
+        self.components.push(Box::new(move |world: &mut World<T>, entity: Entity| {
+            world.add_tag(entity, tag);
+        }));
+        self
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        let name = String::from(name);
+        self.components.push(Box::new(move |world: &mut World<T>, entity: Entity| {
+            world.set_name(entity, &name);
+        }));
+        self
+    }
+
+    pub fn build(self, world: &mut World<T>) -> Entity {
+        let entity = world.create_entity();
+        for add_component in self.components {
+            add_component(world, entity);
+        }
+        entity
+    }
+}
+
+/// Changes to the World recorded while it can only be read, e.g. by systems during
+/// `World::process`. They are applied in order by `World::apply_commands`.
+pub struct CommandBuffer<T> {
+    commands: RefCell<Vec<Command<T>>>,
+}
+
+impl<T: 'static> CommandBuffer<T> {
+    pub fn new() -> Self {
+        CommandBuffer {
+            commands: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn spawn(&self, builder: EntityBuilder<T>) {
+        self.push(move |world: &mut World<T>| {
+            builder.build(world);
+        });
+    }
+
+    pub fn despawn(&self, entity: Entity) {
+        self.push(move |world: &mut World<T>| {
+            world.destroy_entity(entity);
+        });
+    }
+
+    pub fn insert<C: Component>(&self, entity: Entity, component: C) {
+        self.push(move |world: &mut World<T>| {
+            world.add_component_to_entity(entity, component);
+        });
+    }
+
+    pub fn remove<C: Component>(&self, entity: Entity) {
+        self.push(move |world: &mut World<T>| {
+            world.remove_component_from_entity::<C>(entity);
+        });
+    }
+
+    pub fn add_tag(&self, entity: Entity, tag: u32) {
+        self.push(move |world: &mut World<T>| {
+            world.add_tag(entity, tag);
+        });
+    }
+
+    pub fn remove_tag(&self, entity: Entity, tag: u32) {
+        self.push(move |world: &mut World<T>| {
+            world.remove_tag(entity, tag);
+        });
+    }
+
+    pub fn set_name(&self, entity: Entity, name: &str) {
+        let name = String::from(name);
+        self.push(move |world: &mut World<T>| {
+            world.set_name(entity, &name);
+        });
+    }
+
+    /// Records an arbitrary change to the World.
+    pub fn push<F: FnOnce(&mut World<T>) + 'static>(&self, command: F) {
+        self.commands.borrow_mut().push(Box::new(command));
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.borrow().is_empty()
+    }
+
+    /// Takes every recorded command out of the buffer, leaving it empty.
+    pub fn drain(&self) -> Vec<Command<T>> {
+        mem::replace(&mut *self.commands.borrow_mut(), Vec::new())
+    }
+}
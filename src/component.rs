@@ -1,3 +1,5 @@
+use entity::Entity;
+use world::World;
 use std::any::Any;
 use std::collections::HashMap;
 
@@ -7,20 +9,287 @@ pub trait GenericStorage<T> {
     fn new() -> Self
     where
         Self: Sized;
-    fn insert(&mut self, index: usize, value: T) -> usize;
+    /// Stores the value for the given entity index, returning the value it replaced, if any.
+    fn insert(&mut self, index: usize, value: T) -> Option<T>;
     fn get(&self, index: usize) -> Option<&T>;
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>;
     fn len(&self) -> usize;
-    fn all(&self) -> &HashMap<usize, T>;
+    fn contains(&self, index: usize) -> bool;
+    fn indices(&self) -> Vec<usize>;
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a>;
+    fn iter_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = (usize, &'a mut T)> + 'a>;
     fn remove(&mut self, index: usize) -> Option<T>;
 }
 
 pub trait Component: Any + Sized {
-    type Storage: GenericStorage<Self>;
-    /*
-    fn added(&self) {}
-    fn removed(&self) {}
-    fn render(&self) {}
-    */
+    type Storage: GenericStorage<Self> + Any;
+
+    /// Called once the component has been attached to the entity.
+    fn on_added<T: 'static>(_world: &mut World<T>, _entity: Entity) {}
+    /// Called right before the component is detached from the entity, either explicitly
+    /// or because the entity is being destroyed. The component can still be read.
+    fn on_removed<T: 'static>(_world: &mut World<T>, _entity: Entity) {}
+    /// Called for every entity owning the component when its scene begins.
+    fn on_scene_begin<T: 'static>(_world: &mut World<T>, _entity: Entity) {}
+    /// Called for every entity owning the component when its scene ends.
+    fn on_scene_end<T: 'static>(_world: &mut World<T>, _entity: Entity) {}
+}
+
+pub type ComponentHook<T> = fn(&mut World<T>, Entity);
+
+/// The lifecycle hooks of a component type, kept by the World so they can be
+/// run when only the TypeId of the component is known.
+pub struct ComponentHooks<T> {
+    pub on_added: ComponentHook<T>,
+    pub on_removed: ComponentHook<T>,
+    pub on_scene_begin: ComponentHook<T>,
+    pub on_scene_end: ComponentHook<T>,
+}
+
+impl<T: 'static> ComponentHooks<T> {
+    pub fn of<C: Component>() -> Self {
+        ComponentHooks {
+            on_added: C::on_added::<T>,
+            on_removed: C::on_removed::<T>,
+            on_scene_begin: C::on_scene_begin::<T>,
+            on_scene_end: C::on_scene_end::<T>,
+        }
+    }
+}
+
+impl<T> Clone for ComponentHooks<T> {
+    fn clone(&self) -> Self {
+        ComponentHooks {
+            on_added: self.on_added,
+            on_removed: self.on_removed,
+            on_scene_begin: self.on_scene_begin,
+            on_scene_end: self.on_scene_end,
+        }
+    }
+}
+
+impl<T> Copy for ComponentHooks<T> {}
+
+/// Copies and restores the storage of a component type, kept by the World for the
+/// component types registered with `World::register_snapshot_component`.
+#[derive(Copy, Clone)]
+pub struct ComponentSnapshotter {
+    capture: fn(&dyn AnyStorage) -> Box<dyn Any>,
+    restore: fn(&mut dyn AnyStorage, &dyn Any),
+}
+
+impl ComponentSnapshotter {
+    pub fn of<C: Component>() -> Self where C::Storage: Clone {
+        ComponentSnapshotter {
+            capture: capture_storage::<C>,
+            restore: restore_storage::<C>,
+        }
+    }
+
+    /// A copy of the storage, along with its change trackers.
+    pub fn capture(&self, storage: &dyn AnyStorage) -> Box<dyn Any> {
+        (self.capture)(storage)
+    }
+
+    /// Overwrites the storage with a copy taken by `capture`.
+    pub fn restore(&self, storage: &mut dyn AnyStorage, snapshot: &dyn Any) {
+        (self.restore)(storage, snapshot)
+    }
+}
+
+fn capture_storage<C: Component>(storage: &dyn AnyStorage) -> Box<dyn Any> where C::Storage: Clone {
+    Box::new(storage.as_any().downcast_ref::<ComponentStorage<C>>().unwrap().clone())
+}
+
+fn restore_storage<C: Component>(storage: &mut dyn AnyStorage, snapshot: &dyn Any) where C::Storage: Clone {
+    match (storage.as_any_mut().downcast_mut::<ComponentStorage<C>>(), snapshot.downcast_ref::<ComponentStorage<C>>()) {
+        (Some(storage), Some(snapshot)) => {
+            *storage = snapshot.clone();
+        },
+        _ => {}
+    }
+}
+
+/// Ticks at which a component was added to its entity and last borrowed mutably.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ComponentTicks {
+    added: u32,
+    changed: u32,
+}
+
+impl ComponentTicks {
+    pub fn new(tick: u32) -> Self {
+        ComponentTicks {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    pub fn get_added(&self) -> u32 {
+        self.added
+    }
+
+    pub fn get_changed(&self) -> u32 {
+        self.changed
+    }
+
+    pub fn is_added(&self, since: u32) -> bool {
+        self.added > since
+    }
+
+    /// A component that has just been added counts as changed too.
+    pub fn is_changed(&self, since: u32) -> bool {
+        self.changed > since
+    }
+}
+
+/// Type erased access to a component storage. The World uses it whenever it has to
+/// work on every storage without knowing the component types, e.g. when destroying an entity.
+pub trait AnyStorage {
+    fn len(&self) -> usize;
+    fn contains(&self, index: usize) -> bool;
+    fn indices(&self) -> Vec<usize>;
+    fn remove_entity(&mut self, entity: Entity, tick: u32);
+    fn is_added(&self, index: usize, since: u32) -> bool;
+    fn is_changed(&self, index: usize, since: u32) -> bool;
+    /// Forgets the removals recorded up to the given tick.
+    fn clear_removed(&mut self, up_to: u32);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Wraps the storage of a component type and keeps track of when its
+/// components were added, changed and removed.
+pub struct ComponentStorage<C: Component> {
+    storage: C::Storage,
+    ticks: HashMap<usize, ComponentTicks>,
+    removed: Vec<(Entity, u32)>,
+}
+
+impl<C: Component> ComponentStorage<C> {
+    pub fn new() -> Self {
+        ComponentStorage {
+            storage: C::Storage::new(),
+            ticks: HashMap::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Direct access to the storage. Writes made through it aren't tracked.
+    pub fn get_storage(&self) -> &C::Storage {
+        &self.storage
+    }
+
+    pub fn get_storage_mut(&mut self) -> &mut C::Storage {
+        &mut self.storage
+    }
+
+    pub fn insert(&mut self, index: usize, component: C, tick: u32) -> Option<C> {
+        let old = self.storage.insert(index, component);
+        if old.is_some() {
+            self.mark_changed(index, tick);
+        } else {
+            self.ticks.insert(index, ComponentTicks::new(tick));
+        }
+        old
+    }
+
+    pub fn get(&self, index: usize) -> Option<&C> {
+        self.storage.get(index)
+    }
+
+    /// Marks the component as changed at the given tick.
+    pub fn get_mut(&mut self, index: usize, tick: u32) -> Option<&mut C> {
+        self.mark_changed(index, tick);
+        self.storage.get_mut(index)
+    }
+
+    pub fn remove(&mut self, entity: Entity, tick: u32) -> Option<C> {
+        let old = self.storage.remove(entity.get_index());
+        self.ticks.remove(&entity.get_index());
+        if old.is_some() {
+            self.removed.push((entity, tick));
+        }
+        old
+    }
+
+    pub fn mark_changed(&mut self, index: usize, tick: u32) {
+        match self.ticks.get_mut(&index) {
+            Some(ticks) => {
+                ticks.changed = tick;
+            },
+            None => {}
+        }
+    }
+
+    pub fn mark_all_changed(&mut self, tick: u32) {
+        for ticks in self.ticks.values_mut() {
+            ticks.changed = tick;
+        }
+    }
+
+    pub fn get_ticks(&self, index: usize) -> Option<&ComponentTicks> {
+        self.ticks.get(&index)
+    }
+
+    /// Entities that lost the component, with the tick they lost it at.
+    pub fn get_removed(&self) -> &[(Entity, u32)] {
+        &self.removed
+    }
+}
+
+impl<C: Component> Clone for ComponentStorage<C> where C::Storage: Clone {
+    fn clone(&self) -> Self {
+        ComponentStorage {
+            storage: self.storage.clone(),
+            ticks: self.ticks.clone(),
+            removed: self.removed.clone(),
+        }
+    }
+}
+
+impl<C: Component> AnyStorage for ComponentStorage<C> {
+    fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.storage.contains(index)
+    }
+
+    fn indices(&self) -> Vec<usize> {
+        self.storage.indices()
+    }
+
+    fn remove_entity(&mut self, entity: Entity, tick: u32) {
+        self.remove(entity, tick);
+    }
+
+    fn is_added(&self, index: usize, since: u32) -> bool {
+        match self.ticks.get(&index) {
+            Some(ticks) => ticks.is_added(since),
+            None => false
+        }
+    }
+
+    fn is_changed(&self, index: usize, since: u32) -> bool {
+        match self.ticks.get(&index) {
+            Some(ticks) => ticks.is_changed(since),
+            None => false
+        }
+    }
+
+    fn clear_removed(&mut self, up_to: u32) {
+        self.removed.retain(|&(_, tick)| tick > up_to);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 impl<T> GenericStorage<T> for HashMap<usize, T> {
@@ -28,21 +297,36 @@ impl<T> GenericStorage<T> for HashMap<usize, T> {
         return HashMap::new();
     }
 
-    fn insert(&mut self, index: usize, value: T) -> usize {
-        self.insert(index, value);
-        self.len() - 1
+    fn insert(&mut self, index: usize, value: T) -> Option<T> {
+        self.insert(index, value)
     }
 
     fn get(&self, index: usize) -> Option<&T> {
         self.get(&index)
     }
 
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.get_mut(&index)
+    }
+
     fn len(&self) -> usize {
         self.len()
     }
 
-    fn all(&self) -> &HashMap<usize, T> {
-        self
+    fn contains(&self, index: usize) -> bool {
+        self.contains_key(&index)
+    }
+
+    fn indices(&self) -> Vec<usize> {
+        self.keys().cloned().collect()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a> {
+        Box::new(HashMap::iter(self).map(|(index, value)| (*index, value)))
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = (usize, &'a mut T)> + 'a> {
+        Box::new(HashMap::iter_mut(self).map(|(index, value)| (*index, value)))
     }
 
     fn remove(&mut self, index: usize) -> Option<T> {
@@ -120,4 +404,50 @@ impl PartialEq for Component {
         self.id == other.id
     }
 }
-*/
\ No newline at end of file
+*/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the hooks of `Tracked` saw, in order.
+    struct HookLog(Vec<String>);
+
+    struct Tracked;
+
+    impl Component for Tracked {
+        type Storage = HashMap<usize, Self>;
+
+        fn on_added<T: 'static>(world: &mut World<T>, entity: Entity) {
+            world.resource_mut::<HookLog>().unwrap().0.push(format!("added {}", entity));
+        }
+
+        fn on_removed<T: 'static>(world: &mut World<T>, entity: Entity) {
+            let present = world.get_component_for_entity::<Tracked>(entity).is_some();
+            world.resource_mut::<HookLog>().unwrap().0.push(format!("removed {} {}", entity, present));
+        }
+
+        fn on_scene_begin<T: 'static>(world: &mut World<T>, entity: Entity) {
+            world.resource_mut::<HookLog>().unwrap().0.push(format!("begin {}", entity));
+        }
+    }
+
+    #[test]
+    fn hooks_run_once_per_lifecycle_step() {
+        let mut world: World<()> = World::new();
+        world.register_component_with_storage::<Tracked>();
+        world.insert_resource(HookLog(Vec::new()));
+        let a = world.create_entity();
+        let b = world.create_entity();
+        world.add_component_to_entity(a, Tracked);
+        // replacing the component doesn't add it again
+        world.add_component_to_entity(a, Tracked);
+        world.add_component_to_entity(b, Tracked);
+        world.scene_begin();
+        world.remove_component_from_entity::<Tracked>(a);
+        world.remove_component_from_entity::<Tracked>(a);
+        world.destroy_entity(b);
+        // on_removed still sees the component it is about to lose
+        assert_eq!(world.resource::<HookLog>().unwrap().0,
+            vec!["added 0:0", "added 1:0", "begin 0:0", "begin 1:0", "removed 0:0 true", "removed 1:0 true"]);
+    }
+}
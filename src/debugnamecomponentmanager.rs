@@ -4,14 +4,19 @@ use std::collections::HashMap;
 use entity::Entity;
 use log::Log;
 
+#[derive(Clone)]
 pub struct InstanceData {
     names: Vec<String>,
+    entities: Vec<Entity>,
+    unique: Vec<bool>,
 }
 
 impl InstanceData {
     pub fn new() -> Self {
         InstanceData {
             names: Vec::new(),
+            entities: Vec::new(),
+            unique: Vec::new(),
         }
     }
 }
@@ -28,9 +33,14 @@ impl Instance {
     }
 }
 
+/// Names of the entities. Several entities can share a name unless it was given as unique.
+/// Every World owns one, the entries of destroyed entities are freed and reused.
+#[derive(Clone)]
 pub struct DebugNameComponentManager {
     data: InstanceData,
-    map: HashMap<Entity, usize>, 
+    map: HashMap<Entity, usize>,
+    by_name: HashMap<String, Vec<Entity>>,
+    free: Vec<usize>,
 }
 
 impl DebugNameComponentManager {
@@ -38,6 +48,8 @@ impl DebugNameComponentManager {
         DebugNameComponentManager {
             data: InstanceData::new(),
             map: HashMap::new(),
+            by_name: HashMap::new(),
+            free: Vec::new(),
         }
     }
 
@@ -60,23 +72,123 @@ impl DebugNameComponentManager {
         self.make_instance(i)
     }
 
+    /// The instance of the entity, created with the name "empty" if it doesn't have one yet.
     pub fn create(&mut self, entity: Entity) -> Instance {
-        self.data.names.push(String::from("empty"));
-        self.map.entry(entity).or_insert(self.data.names.len()-1);
-        self.make_instance(self.data.names.len()-1)
+        if let Some(i) = self.map.get(&entity) {
+            return self.make_instance(*i);
+        }
+        let name = String::from("empty");
+        let i = match self.free.pop() {
+            Some(i) => {
+                self.data.names[i] = name.clone();
+                self.data.entities[i] = entity;
+                self.data.unique[i] = false;
+                i
+            },
+            None => {
+                self.data.names.push(name.clone());
+                self.data.entities.push(entity);
+                self.data.unique.push(false);
+                self.data.names.len() - 1
+            }
+        };
+        self.map.insert(entity, i);
+        self.by_name.entry(name).or_insert_with(Vec::new).push(entity);
+        self.make_instance(i)
     }
 
     pub fn get_name(&self, i: Instance) -> &String {
         &self.data.names[i.i]
     }
 
-    pub fn set_name(&mut self, i: Instance, name: String) {
-        self.data.names[i.i] = name;
+    /// Returns false, leaving the name as it was, if another entity has the name as a unique one.
+    pub fn set_name(&mut self, i: Instance, name: String) -> bool {
+        self.rename(i.i, name, false)
+    }
+
+    /// Returns false, leaving the name as it was, if another entity already has the name.
+    pub fn set_unique_name(&mut self, i: Instance, name: String) -> bool {
+        self.rename(i.i, name, true)
+    }
+
+    fn rename(&mut self, i: usize, name: String, unique: bool) -> bool {
+        let entity = self.data.entities[i];
+        let taken = match self.by_name.get(&name) {
+            Some(entities) => {
+                entities.iter().any(|e| *e != entity && (unique || self.data.unique[self.map[e]]))
+            },
+            None => false
+        };
+        if taken {
+            Log::warning(&format!("The name {} is already taken", name));
+            return false;
+        }
+        self.unlink(i);
+        self.by_name.entry(name.clone()).or_insert_with(Vec::new).push(entity);
+        self.data.names[i] = name;
+        self.data.unique[i] = unique;
+        true
+    }
+
+    fn unlink(&mut self, i: usize) {
+        let entity = self.data.entities[i];
+        let empty = match self.by_name.get_mut(&self.data.names[i]) {
+            Some(entities) => {
+                entities.retain(|e| *e != entity);
+                entities.is_empty()
+            },
+            None => false
+        };
+        if empty {
+            self.by_name.remove(&self.data.names[i]);
+        }
+    }
+
+    /// Frees the name of the entity.
+    pub fn remove(&mut self, entity: Entity) {
+        let i = match self.map.remove(&entity) {
+            Some(i) => i,
+            None => return
+        };
+        self.unlink(i);
+        self.data.names[i].clear();
+        self.data.unique[i] = false;
+        self.free.push(i);
+    }
+
+    pub fn get_entity_name(&self, entity: Entity) -> Option<&str> {
+        self.map.get(&entity).map(|i| self.data.names[*i].as_str())
+    }
+
+    /// The first entity that got the name.
+    pub fn find(&self, name: &str) -> Option<Entity> {
+        self.find_all(name).first().cloned()
+    }
+
+    /// The entities with the name, in the order they got it.
+    pub fn find_all(&self, name: &str) -> &[Entity] {
+        match self.by_name.get(name) {
+            Some(entities) => entities,
+            None => &[]
+        }
+    }
+
+    /// `name (index:generation)`, or just the id if the entity has no name. For logs and debug overlays.
+    pub fn get_label(&self, entity: Entity) -> String {
+        match self.get_entity_name(entity) {
+            Some(name) => format!("{} ({})", name, entity),
+            None => entity.to_string()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
     }
 
+    /// Logs every named entity.
     pub fn update(&self, dt: f32) {
-        for name in &self.data.names {
-            Log::debug(&name);
+        for (entity, i) in &self.map {
+            Log::debug(&format!("{} ({})", self.data.names[*i], entity));
         }
     }
 }
@@ -0,0 +1,33 @@
+use component::Component;
+use storage::DenseVecStorage;
+
+/// Draw order of an entity, set with `Scene::set_depth`. Entities with a higher depth
+/// are drawn first, behind the others. Entities without one have depth 0.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DepthComponent {
+    depth: i32,
+    actual_depth: f64,
+}
+
+impl Component for DepthComponent {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl DepthComponent {
+    pub fn new(depth: i32, actual_depth: f64) -> Self {
+        DepthComponent {
+            depth: depth,
+            actual_depth: actual_depth,
+        }
+    }
+
+    pub fn get_depth(&self) -> i32 {
+        return self.depth;
+    }
+
+    /// The depth minus a small amount that is unique among the entities of the scene
+    /// with the same depth, so that the ones that got it later are drawn on top.
+    pub fn get_actual_depth(&self) -> f64 {
+        return self.actual_depth;
+    }
+}
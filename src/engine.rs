@@ -12,6 +12,8 @@ extern crate rand;
 #[cfg(feature = "hotload")]
 use dynamic_reload::{DynamicReload, Lib, Symbol, Search, PlatformName, UpdateState};
 use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::time::Duration;
 use std::thread;
 use std::str;
@@ -41,23 +43,24 @@ use camera::Camera;
 use viewportadapter::ScalingViewportAdapter;
 use viewportadapter::ViewportAdapterTrait;
 use scene::Scene;
+use scenemanager::{SceneManager, TransitionKind};
 use imagecomponent::ImageComponent;
 use log::Log;
 use everythingrenderer::EverythingRenderer;
-use debugnamecomponentmanager::DebugNameComponentManager;
 use rectangle::Rectangle;
 use timer;
 use timer::Timer;
 use texture::Texture;
-use transformcomponent::TransformComponent;
+use transformcomponent::{GlobalTransformComponent, TransformComponent};
 use crate::atlas::texturepacker;
+use crate::atlas::atlasmanager::AtlasManager;
 use spritecomponent::{SpriteComponent, SpriteFrame};
 use subtexture::Subtexture;
 use tile::layercomponent::LayerComponent;
 use render_target::RenderTarget;
 use utils;
 use graphicsdevice::GraphicsDevice;
-use world::{BaseSystem, System, SystemData};
+use world::{BaseSystem, Stage, System, SystemData};
 use entity::Entity;
 use self::cgmath::{Vector2, Vector3, Matrix, Matrix4, One};
 
@@ -210,6 +213,9 @@ fn build_scaling_viewport(window_width: u32, window_height: u32,
   scale_matrix.w = out_matrix.w;
 }
 
+/// Time elapsed since the previous frame, in seconds. Kept up to date as a scene resource.
+pub struct DeltaTime(pub f32);
+
 pub struct MainLoopContext {
     running: bool,
     current_frame_delta: u64,
@@ -219,7 +225,6 @@ pub struct MainLoopContext {
     event_pump: sdl2::EventPump,
     last_time: u64,
     sb: SpriteBatch,
-    debug_name_manager: DebugNameComponentManager,
     framerate: u64,
     shader: Shader,
     bunnies: Vec<Bunny>,
@@ -259,14 +264,14 @@ impl SystemData for MainLoopContext {
 
 pub struct Engine {
     main_loop_context: Option<MainLoopContext>,
-    scene: Option<Scene<MainLoopContext>>,
+    scenes: SceneManager<MainLoopContext>,
 }
 
 impl Engine {
     pub fn new() -> Self {
         Engine {
             main_loop_context: None,
-            scene: None,
+            scenes: SceneManager::new(),
         }
     }
 
@@ -289,12 +294,17 @@ impl Engine {
         }
     }
 
+    /// The stack of scenes, to push, pop and replace them with or without a transition.
+    pub fn get_scene_manager(&mut self) -> &mut SceneManager<MainLoopContext> {
+        &mut self.scenes
+    }
+
     pub fn main_loop(&mut self) {
         let ref mut main_loop_context = self.main_loop_context;
         //let main_loop_context = &mut self.main_loop_context;
         match main_loop_context {
             Some(main_loop_context) => {
-                match &mut self.scene {
+                match self.scenes.get_top_mut() {
                     Some(scene) => {
                         let sh = main_loop_context.shader.clone();
 
@@ -330,7 +340,9 @@ impl Engine {
                                 },
                                 Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
                                     Log::info("Space pressed");
-                                    scene.destroy_entity(1);
+                                    if let Some(entity) = scene.find_by_name("entity2") {
+                                        scene.destroy_entity(entity);
+                                    }
                                 }
                                 _ => {}
                             }
@@ -377,7 +389,22 @@ impl Engine {
                         let current_time = timer::precise_time_ns();
                         let delta_time = ((current_time - main_loop_context.last_time) as f64) / 1_000_000_000.0;
                         main_loop_context.last_time = current_time;
-                        {
+                        if let Some(mut dt) = scene.resource_mut::<DeltaTime>() {
+                            dt.0 = delta_time as f32;
+                        }
+                        scene.before_update(delta_time as f32, main_loop_context);
+                        scene.update(delta_time as f32, main_loop_context);
+                        scene.after_update(delta_time as f32, main_loop_context);
+                        self.scenes.update_transition(delta_time as f32);
+
+                        // the bunnies are part of the top scene's logic, they step once per frame
+                        for bunny in main_loop_context.bunnies.iter_mut() {
+                            bunny.update(delta_time);
+                        }
+
+                        // paused scenes that render when paused are drawn below the top one
+                        let visible_scenes = self.scenes.get_visible_scenes();
+                        for (index, scene) in visible_scenes.iter().enumerate() {
                             let position = Vector2::new(0.0, 0.0);
                             let matrix: Matrix4<f32> = Matrix4::one();
                             //sdl2::log::log("wabbit width and height follows");
@@ -392,39 +419,42 @@ impl Engine {
                             let camera_matrix = main_loop_context.camera.get_transform_matrix();
                             println!("{:?}", camera_matrix);
                             main_loop_context.sb.begin(viewport, SpriteSortMode::SpriteSortModeDeferred, Some(main_loop_context.shader), Some(camera_matrix));
-                            {
-                                let e = 4;
-                                let ic_compo = scene.get_component::<ImageComponent>(e);
-                                let tc_compo = scene.get_component::<TransformComponent>(e);
-                                let ic = ic_compo.unwrap();
-                                let tc = tc_compo.unwrap();
-                                let tex = ic.get_texture().unwrap();
-                                let mut scale = Vector2::new(3.0, 3.0);
-                                main_loop_context.sb.draw(tex, Some(*tc.get_position()), None, None, None, 0.0, Some(scale), Color::white(), 0.0);
+                            let background = scene.find_by_name("background");
+                            if let Some(e) = background {
+                                match (scene.get_component::<ImageComponent>(e), scene.get_component::<TransformComponent>(e)) {
+                                    (Some(ic), Some(tc)) => {
+                                        match ic.get_texture() {
+                                            Some(tex) => {
+                                                let scale = Vector2::new(3.0, 3.0);
+                                                main_loop_context.sb.draw(tex, Some(*tc.get_position()), None, None, None, 0.0, Some(scale), Color::white(), 0.0);
+                                            },
+                                            None => {}
+                                        }
+                                    },
+                                    _ => {}
+                                }
                             }
         
-                            for bunny in main_loop_context.bunnies.iter_mut() {
-                                bunny.update(delta_time);
-                                main_loop_context.sb.draw(main_loop_context.wabbit.clone(), Some(bunny.position), None, None, None, 0.0, None, Color::white(), 0.0);
+                            if index == visible_scenes.len() - 1 {
+                                for bunny in main_loop_context.bunnies.iter() {
+                                    main_loop_context.sb.draw(main_loop_context.wabbit.clone(), Some(bunny.position), None, None, None, 0.0, None, Color::white(), 0.0);
+                                }
                             }
         
-                            {
-                                let e = 0;
-                                let ic_compo = scene.get_component::<ImageComponent>(e);
-                                let tc_compo = scene.get_component::<TransformComponent>(e);
-                                let ic = ic_compo.unwrap();
-                                let tc = tc_compo.unwrap();
-                                let tex = ic.get_texture().unwrap();
-                                main_loop_context.sb.draw(tex, Some(*tc.get_position()), None, None, None, 0.0, None, Color::white(), 0.0);
-                            }
-                            {
-                                let e = 1;
-                                let ic_compo = scene.get_component::<ImageComponent>(e);
-                                let tc_compo = scene.get_component::<TransformComponent>(e);
-                                let ic = ic_compo.unwrap();
-                                let tc = tc_compo.unwrap();
-                                let tex = ic.get_texture().unwrap();
-                                main_loop_context.sb.draw(tex, Some(*tc.get_position()), None, None, None, 0.0, None, Color::white(), 0.0);
+                            let images: Vec<Entity> = scene.query::<(&GlobalTransformComponent, &ImageComponent)>().iter().map(|(e, _)| e).collect();
+                            for e in scene.get_entities_in_draw_order(&images) {
+                                // the background has already been drawn behind the bunnies
+                                if Some(e) == background {
+                                    continue;
+                                }
+                                let tc = scene.get_component::<GlobalTransformComponent>(e).unwrap();
+                                let ic = scene.get_component::<ImageComponent>(e).unwrap();
+                                match ic.get_texture() {
+                                    Some(tex) => {
+                                        main_loop_context.sb.draw(tex, Some(*tc.get_position()), None, None, None, 0.0, None, Color::white(), 0.0);
+                                    },
+                                    None => {}
+                                }
                             }
                             /*
                             {
@@ -440,36 +470,6 @@ impl Engine {
                                 main_loop_context.sb.draw(tex, Some(*tc.get_position()), None, Some(*clip_rect), None, 0.0, None, Color::white(), 0.0);
                             }
                             */
-                            {
-                                let e = 3;
-                                let lc_compo = scene.get_component::<LayerComponent>(e);
-                                let tc_compo = scene.get_component::<TransformComponent>(e);
-                                let lc = lc_compo.unwrap();
-                                let tc = tc_compo.unwrap();
-                                let tex = lc.get_texture().unwrap();
-                                let layer = lc.get_layer().clone().unwrap();
-                                match layer.layer_type {
-                                    tiled_json_rs::LayerType::TileLayer(tiles) => {
-                                        let mut count = 0;
-                                        for tile in tiles.data {
-                                            if tile != 0 && tile != 2684354639 {
-                                                let gid = tile-1;
-                                                let clip_rect = Rectangle::new((gid % 25 * 16) as f32, (gid / 25 * 16) as f32, 16, 16);
-                                                //let clip_rect = Rectangle::new(16.0, 16.0, 16, 16);
-                                                let mut pos = (*tc.get_position()).clone();
-                                                pos.x = pos.x + (count % 58 * 16) as f32;
-                                                pos.y = pos.y + (count / 58 * 16) as f32;
-                                                //println!("{}", tile);
-                                                main_loop_context.sb.draw(tex.clone(), Some(pos), None, Some(clip_rect), None, 0.0, None, Color::white(), 0.0);
-                                            }
-                                            count += 1;
-                                        }
-                                        //let clip_rect = sc.get_source_rect();
-                                        //main_loop_context.sb.draw(tex, Some(tc.get_position()), None, Some(*clip_rect), None, 0.0, None, Color::white(), 0.0);
-                                    }
-                                    _ => {}
-                                }
-                            }
                             /*
                             {
                                 let e = 5;
@@ -484,13 +484,11 @@ impl Engine {
                                 main_loop_context.sb.draw(tex, Some(*tc.get_position()), None, Some(*clip_rect), None, 0.0, None, Color::white(), 0.0);
                             }
                             */
-                            scene.process(delta_time as f32, main_loop_context);
+                            scene.process_stage(Stage::Render, delta_time as f32, main_loop_context);
                             main_loop_context.sb.end(viewport);
+                            scene.render_entities();
                         }
         
-                        main_loop_context.debug_name_manager.update(0.0);
-                        scene.render_entities();
-        
                         main_loop_context.sb.get_graphics_device_mut().apply_shader(&main_loop_context.quad_shader);
                         let mut vp = Rectangle::new(0.0, 0.0, 0, 0);
                         let mut multiplier: f32 = 1.0;
@@ -502,6 +500,18 @@ impl Engine {
                         GraphicsDevice::set_uniform_mat4(&main_loop_context.quad_shader, "transform", camera_transform_mat);
                         GraphicsDevice::set_uniform_float2(&main_loop_context.quad_shader, "scale", multiplier, multiplier);
                         GraphicsDevice::set_uniform_float2(&main_loop_context.quad_shader, "viewport", vp.x, vp.y);
+                        let (fade, wipe, transition_color) = match self.scenes.get_transition() {
+                            Some(transition) => {
+                                match transition.get_kind() {
+                                    TransitionKind::Fade => (transition.get_amount(), 0.0, transition.get_color()),
+                                    TransitionKind::Wipe => (0.0, transition.get_amount(), transition.get_color()),
+                                }
+                            },
+                            None => (0.0, 0.0, Color::black())
+                        };
+                        GraphicsDevice::set_uniform_float(&main_loop_context.quad_shader, "fade", fade);
+                        GraphicsDevice::set_uniform_float(&main_loop_context.quad_shader, "wipe", wipe);
+                        GraphicsDevice::set_uniform_float4(&main_loop_context.quad_shader, "transition_color", transition_color.get_r(), transition_color.get_g(), transition_color.get_b(), transition_color.get_a());
                         GraphicsDevice::draw_quad_to_screen(&main_loop_context.quad_shader, &main_loop_context.screen_render_target);
         
         
@@ -736,104 +746,80 @@ impl Engine {
         let mut player_camera = Camera::new();
         player_camera.set_viewport_adapter(Some(player_va));
 
-        let mut debug_name_manager = DebugNameComponentManager::new();
-
         let mut scene = Scene::new(32);
         scene.register_component::<ImageComponent>();
         scene.register_component::<TransformComponent>();
+        scene.register_component::<GlobalTransformComponent>();
+        scene.register_serializable_component::<TransformComponent>();
+        scene.register_snapshot_component::<TransformComponent>();
+        scene.register_snapshot_component::<GlobalTransformComponent>();
         scene.register_component::<SpriteComponent>();
         scene.register_component::<LayerComponent>();
 
+        let mut layer_system = LayerSystem::new();
+        layer_system.base.watch_component::<LayerComponent>();
+        layer_system.base.watch_component::<GlobalTransformComponent>();
+        scene.add_system_to_stage(Stage::Render, layer_system);
+
         let mut sprite_system = SpriteSystem::new();
         sprite_system.base.watch_component::<SpriteComponent>();
-        scene.add_system(sprite_system);
+        sprite_system.base.watch_component::<GlobalTransformComponent>();
+        scene.add_system_to_stage(Stage::Render, sprite_system);
+
+        scene.insert_resource(tm);
+        let mut am = AtlasManager::new();
+        am.add(String::from("props"), String::from("props"), props_atlas);
+        am.add(String::from("atlas"), String::from("atlas"), atlas);
+        scene.insert_resource(am);
+
+        scene.register_prefab_component::<TransformComponent>();
+        scene.register_prefab_component::<ImageComponent>();
+        scene.register_prefab_component::<SpriteComponent>();
+        for name in &["prop", "wabbit", "block", "back", "player"] {
+            let prefab_path = [self.assets_path(), String::from("prefabs/"), name.to_string(), String::from(".json")].concat();
+            scene.load_prefab_file(name, Path::new(&prefab_path));
+        }
 
         {
-            let entity_id = scene.create_entity();
-            sdl2::log::log("Entity id follows");
-            sdl2::log::log(&entity_id.to_string());
-            let debug_name_instance = debug_name_manager.create(entity_id);
-            Log::debug("Debug name instance");
-            Log::debug(&debug_name_instance.i.to_string());
-            debug_name_manager.set_name(debug_name_instance, String::from("entity1"));
-
-            let mut ic = ImageComponent::new();//with_texture(tm.get(&String::from("wabbit")));
-            ic.texture = Some(tm.get(&String::from("wabbit")));
-            scene.add_component_to_entity(entity_id, ic);
-
-            let mut tc = TransformComponent::new();
-            tc.set_position(50.0, 50.0);
-            scene.add_component_to_entity(entity_id, tc);
+            match scene.spawn_prefab_at("wabbit", 50.0, 50.0) {
+                Some(entity_id) => {
+                    scene.set_name(entity_id, "entity1");
+                    sdl2::log::log(&format!("Created {}", scene.get_debug_label(entity_id)));
+                },
+                None => {
+                    Log::error("Cannot spawn the wabbit prefab");
+                }
+            }
         }
         {
-            let entity_id = scene.create_entity();
-            sdl2::log::log("Entity id follows");
-            sdl2::log::log(&entity_id.to_string());
-            let debug_name_instance = debug_name_manager.create(entity_id);
-            Log::debug("Debug name instance");
-            Log::debug(&debug_name_instance.i.to_string());
-            debug_name_manager.set_name(debug_name_instance, String::from("entity2"));
-
-            let mut ic = ImageComponent::new();
-            ic.texture = Some(tm.get(&String::from("wabbit")));
-            scene.add_component_to_entity(entity_id, ic);
-
-            let mut tc = TransformComponent::new();
-            tc.set_position(100.0, 50.0);
-            scene.add_component_to_entity(entity_id, tc);
+            match scene.spawn_prefab_at("wabbit", 100.0, 50.0) {
+                Some(entity_id) => {
+                    scene.set_name(entity_id, "entity2");
+                    sdl2::log::log(&format!("Created {}", scene.get_debug_label(entity_id)));
+                },
+                None => {
+                    Log::error("Cannot spawn the wabbit prefab");
+                }
+            }
         }
         {
-            let mut tx = 0.0;
-            let mut ty = 0.0;
-            let mut tx2 = 1.0;
-            let mut ty2 = 1.0;
-            let region = props_atlas
-                .frames
-                .iter()
-                .filter(|frame| frame.filename == "block")
-                .collect::<Vec<&crate::atlas::texturepacker::Frame>>()[0];
-            let sw = props_atlas.meta.size.w as f32;
-            let sh = props_atlas.meta.size.h as f32;
-            /*
-            tx = region.frame.x as f32 / sw;
-            ty = region.frame.y as f32 / sh;
-            tx2 = (region.frame.x as f32 + region.frame.w as f32) / sw;
-            ty2 = (region.frame.y as f32 + region.frame.h as f32) / sh;
-            */
-            tx = region.frame.x as f32;
-            ty = region.frame.y as f32;
-            tx2 = region.frame.w as f32;
-            ty2 = region.frame.h as f32;
-
-            let entity_id = scene.create_entity();
-            sdl2::log::log("Entity id follows");
-            sdl2::log::log(&entity_id.to_string());
-            let debug_name_instance = debug_name_manager.create(entity_id);
-            Log::debug("Debug name instance");
-            Log::debug(&debug_name_instance.i.to_string());
-            debug_name_manager.set_name(debug_name_instance, String::from("entity3"));
-
-            let mut sc = SpriteComponent::new();
-            let sub = Subtexture::with_texture(Some(tm.get(&String::from("props"))), tx as i32, ty as i32, tx2 as i32, ty2 as i32);
-            sc.add_frame_with_subtexture(sub);
-            scene.add_component_to_entity(entity_id, sc);
-
-            let mut tc = TransformComponent::new();
-            tc.set_position(150.0, 50.0);
-            scene.add_component_to_entity(entity_id, tc);
-
+            match scene.spawn_prefab_at("block", 150.0, 50.0) {
+                Some(entity_id) => {
+                    scene.set_name(entity_id, "entity3");
+                    sdl2::log::log(&format!("Created {}", scene.get_debug_label(entity_id)));
+                },
+                None => {
+                    Log::error("Cannot spawn the block prefab");
+                }
+            }
         }
         {
             let entity_id = scene.create_entity();
-            sdl2::log::log("Entity id follows");
-            sdl2::log::log(&entity_id.to_string());
-            let debug_name_instance = debug_name_manager.create(entity_id);
-            Log::debug("Debug name instance");
-            Log::debug(&debug_name_instance.i.to_string());
-            debug_name_manager.set_name(debug_name_instance, String::from("entity3"));
+            scene.set_name(entity_id, "entity3");
+            sdl2::log::log(&format!("Created {}", scene.get_debug_label(entity_id)));
 
             let mut lc = LayerComponent::new();
-            lc.set_texture(Some(tm.get(&String::from("tileset"))));
+            lc.set_texture(scene.resource::<TextureManager>().unwrap().find("tileset"));
             lc.set_layer(Some(map.layers[0].clone()));
             scene.add_component_to_entity(entity_id, lc);
 
@@ -842,69 +828,36 @@ impl Engine {
             scene.add_component_to_entity(entity_id, tc);
         }
         {
-            let entity_id = scene.create_entity();
-            sdl2::log::log("Entity id follows");
-            sdl2::log::log(&entity_id.to_string());
-            let debug_name_instance = debug_name_manager.create(entity_id);
-            Log::debug("Debug name instance");
-            Log::debug(&debug_name_instance.i.to_string());
-            debug_name_manager.set_name(debug_name_instance, String::from("entity4"));
-
-            let mut ic = ImageComponent::new();
-            ic.texture = Some(tm.get(&String::from("back")));
-            scene.add_component_to_entity(entity_id, ic);
-
-            let mut tc = TransformComponent::new();
-            tc.set_position(0.0, 0.0);
-            scene.add_component_to_entity(entity_id, tc);
+            match scene.spawn_prefab_at("back", 0.0, 0.0) {
+                Some(entity_id) => {
+                    scene.set_unique_name(entity_id, "background");
+                    sdl2::log::log(&format!("Created {}", scene.get_debug_label(entity_id)));
+                },
+                None => {
+                    Log::error("Cannot spawn the back prefab");
+                }
+            }
         }
         {
-            let mut tx = 0.0;
-            let mut ty = 0.0;
-            let mut tx2 = 1.0;
-            let mut ty2 = 1.0;
-            let region = atlas
-                .frames
-                .iter()
-                .filter(|frame| frame.filename == "player/idle/player-idle-1")
-                .collect::<Vec<&crate::atlas::texturepacker::Frame>>()[0];
-            let sw = atlas.meta.size.w as f32;
-            let sh = atlas.meta.size.h as f32;
-            /*
-            tx = region.frame.x as f32 / sw;
-            ty = region.frame.y as f32 / sh;
-            tx2 = (region.frame.x as f32 + region.frame.w as f32) / sw;
-            ty2 = (region.frame.y as f32 + region.frame.h as f32) / sh;
-            */
-            tx = region.frame.x as f32;
-            ty = region.frame.y as f32;
-            tx2 = region.frame.w as f32;
-            ty2 = region.frame.h as f32;
-
-            let entity_id = scene.create_entity();
-            sdl2::log::log("Entity id follows");
-            sdl2::log::log(&entity_id.to_string());
-            let debug_name_instance = debug_name_manager.create(entity_id);
-            Log::debug("Debug name instance");
-            Log::debug(&debug_name_instance.i.to_string());
-            debug_name_manager.set_name(debug_name_instance, String::from("entity5"));
-
-            let mut sc = SpriteComponent::new();
-            let sub = Subtexture::with_texture(Some(tm.get(&String::from("atlas"))), tx as i32, ty as i32, tx2 as i32, ty2 as i32);
-            sc.add_frame_with_subtexture(sub);
-            scene.add_component_to_entity(entity_id, sc);
-
-            let mut tc = TransformComponent::new();
             let x = 54.0 * 16.0;
             let y = 9.0 * 16.0;
-            tc.set_position(x, y);
-            scene.add_component_to_entity(entity_id, tc);
+            match scene.spawn_prefab_at("player", x, y) {
+                Some(entity_id) => {
+                    scene.set_name(entity_id, "entity5");
+                    sdl2::log::log(&format!("Created {}", scene.get_debug_label(entity_id)));
+                },
+                None => {
+                    Log::error("Cannot spawn the player prefab");
+                }
+            }
             player_camera.set_position(x - 320.0/2.0, y - 240.0/2.0);
         }
 
         let er = EverythingRenderer::new();
         scene.add_renderer(Rc::new(er));
 
+        scene.insert_resource(DeltaTime(0.0));
+
         let mut rng = rand::thread_rng();
 
 
@@ -952,7 +905,6 @@ impl Engine {
             event_pump: event_pump,
             last_time: last_time,
             sb: sb,
-            debug_name_manager: debug_name_manager,
             framerate: framerate,
             shader: shader,
             bunnies: bunnies,
@@ -963,7 +915,7 @@ impl Engine {
             screen_render_target: screen_render_target,
             quad_shader: quad_shader,
         });
-        self.scene = Some(scene);
+        self.scenes.push(scene);
 
         //
         // While this is running (printing a number) change return value in file src/test_shared.rs
@@ -991,7 +943,7 @@ pub struct SpriteSystem {
 }
 
 impl System<MainLoopContext> for SpriteSystem {
-    fn get_entities(&self) -> Vec<Entity> {
+    fn get_entities(&self) -> &[Entity] {
         return self.base.get_entities();
     }
 
@@ -999,7 +951,7 @@ impl System<MainLoopContext> for SpriteSystem {
         //println!("SpriteSystem process()");
         let main_loop_data = user_data;
         let sc_compo = &mut scene.get_component::<SpriteComponent>(entity);
-        let tc_compo = &mut scene.get_component::<TransformComponent>(entity);
+        let tc_compo = &mut scene.get_component::<GlobalTransformComponent>(entity);
         let sc = sc_compo.unwrap();
         let tc = tc_compo.unwrap();
         let current_frame = sc.get_current_frame();
@@ -1009,13 +961,17 @@ impl System<MainLoopContext> for SpriteSystem {
         main_loop_data.sb.draw(tex, Some(*tc.get_position()), None, Some(*clip_rect), None, 0.0, None, Color::white(), 0.0);
     }
 
-    fn get_components(&self) -> Vec<TypeId> {
+    fn get_components(&self) -> &[TypeId] {
         return self.base.get_components();
     }
 
     fn add_entity(&mut self, entity: Entity) {
         self.base.add_entity(entity);
     }
+
+    fn remove_entity(&mut self, entity: Entity) {
+        self.base.remove_entity(entity);
+    }
 }
 
 impl SpriteSystem {
@@ -1026,6 +982,83 @@ impl SpriteSystem {
     }
 }
 
+/// Draws the tile layers. The tiles of a layer are only worked out again when its LayerComponent changes.
+pub struct LayerSystem {
+    base: BaseSystem,
+    tiles: RefCell<HashMap<Entity, Vec<(Vector2<f32>, Rectangle)>>>,
+}
+
+impl LayerSystem {
+    pub fn new() -> Self {
+        LayerSystem {
+            base: BaseSystem::new(),
+            tiles: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Offset from the layer position and clip rectangle of every tile to draw.
+    fn build_tiles(lc: &LayerComponent) -> Vec<(Vector2<f32>, Rectangle)> {
+        let mut result = Vec::new();
+        let layer = match lc.get_layer() {
+            &Some(ref layer) => layer.clone(),
+            &None => return result
+        };
+        match layer.layer_type {
+            tiled_json_rs::LayerType::TileLayer(tiles) => {
+                let mut count = 0;
+                for tile in tiles.data {
+                    if tile != 0 && tile != 2684354639 {
+                        let gid = tile-1;
+                        let clip_rect = Rectangle::new((gid % 25 * 16) as f32, (gid / 25 * 16) as f32, 16, 16);
+                        let offset = Vector2::new((count % 58 * 16) as f32, (count / 58 * 16) as f32);
+                        result.push((offset, clip_rect));
+                    }
+                    count += 1;
+                }
+            }
+            _ => {}
+        }
+        result
+    }
+}
+
+impl System<MainLoopContext> for LayerSystem {
+    fn get_entities(&self) -> &[Entity] {
+        return self.base.get_entities();
+    }
+
+    fn process(&self, entity: Entity, dt: f32, scene: &Scene<MainLoopContext>, user_data: &mut MainLoopContext) {
+        let main_loop_data = user_data;
+        let lc = scene.get_component::<LayerComponent>(entity).unwrap();
+        let tc = scene.get_component::<GlobalTransformComponent>(entity).unwrap();
+        let tex = match lc.get_texture() {
+            Some(tex) => tex,
+            None => return
+        };
+        let mut tiles = self.tiles.borrow_mut();
+        if scene.is_changed::<LayerComponent>(entity) || !tiles.contains_key(&entity) {
+            tiles.insert(entity, LayerSystem::build_tiles(lc));
+        }
+        for &(offset, clip_rect) in &tiles[&entity] {
+            let pos = *tc.get_position() + offset;
+            main_loop_data.sb.draw(tex.clone(), Some(pos), None, Some(clip_rect), None, 0.0, None, Color::white(), 0.0);
+        }
+    }
+
+    fn get_components(&self) -> &[TypeId] {
+        return self.base.get_components();
+    }
+
+    fn add_entity(&mut self, entity: Entity) {
+        self.base.add_entity(entity);
+    }
+
+    fn remove_entity(&mut self, entity: Entity) {
+        self.base.remove_entity(entity);
+        self.tiles.get_mut().remove(&entity);
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 struct Bunny {
     position: Vector2<f32>,
@@ -2,13 +2,46 @@ extern crate cgmath;
 
 use component::Component;
 use colliderlist::ColliderList;
+use bit_set::BitSet;
 use std::vec::Vec;
 use std::rc::Rc;
 use std::any::TypeId;
+use std::fmt;
 use self::cgmath::Vector2;
 
-pub type Entity = usize;
+/// Handle to an entity living in a `World`.
+/// The index points at the slot in the world's entity list while the generation
+/// tells apart entities that reused the same slot after one of them got destroyed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Entity {
+    index: usize,
+    generation: u32,
+}
+
+impl Entity {
+    pub fn new(index: usize, generation: u32) -> Self {
+        Entity {
+            index: index,
+            generation: generation,
+        }
+    }
+
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.index, self.generation)
+    }
+}
 
+#[derive(Clone)]
 pub struct EntityComponent {
     component_type: TypeId,
     component_index: usize,
@@ -22,6 +55,13 @@ impl EntityComponent {
         }
     }
 
+    pub fn with_type(component_type: TypeId, index: usize) -> Self {
+        EntityComponent {
+            component_type: component_type,
+            component_index: index,
+        }
+    }
+
     pub fn get_component_type(&self) -> &TypeId {
         return &self.component_type;
     }
@@ -31,17 +71,49 @@ impl EntityComponent {
     }
 }
 
+#[derive(Clone)]
 pub struct EntityData {
+    generation: u32,
+    alive: bool,
     components: Vec<EntityComponent>,
+    mask: BitSet,
+    tags: u32,
 }
 
 impl EntityData {
     pub fn new() -> Self {
         EntityData {
+            generation: 0,
+            alive: true,
             components: Vec::new(),
+            mask: BitSet::new(),
+            tags: 0,
         }
     }
 
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    /// Marks the slot as free and bumps its generation so that every handle
+    /// pointing at the old entity becomes stale.
+    pub fn kill(&mut self) {
+        self.alive = false;
+        self.generation = self.generation.wrapping_add(1);
+        self.components.clear();
+        self.mask.clear();
+        self.tags = 0;
+    }
+
+    /// Brings a free slot back to life for a new entity.
+    pub fn revive(&mut self) {
+        self.alive = true;
+    }
+
     pub fn get_components(&self) -> &Vec<EntityComponent> {
         return &self.components;
     }
@@ -50,6 +122,24 @@ impl EntityData {
         return &mut self.components;
     }
 
+    /// Set of the ComponentIds of the components attached to the entity.
+    pub fn get_mask(&self) -> &BitSet {
+        return &self.mask;
+    }
+
+    pub fn get_mask_mut(&mut self) -> &mut BitSet {
+        return &mut self.mask;
+    }
+
+    /// Bitmask of the tags of the entity, see `taglists::tag_mask`.
+    pub fn get_tags(&self) -> u32 {
+        return self.tags;
+    }
+
+    pub fn set_tags(&mut self, tags: u32) {
+        self.tags = tags;
+    }
+
 }
 /*
 pub struct Entity {
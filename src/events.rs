@@ -0,0 +1,218 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::vec::Vec;
+
+/// Handle to the read cursor of a system on an event channel.
+pub struct EventReader<E> {
+    id: usize,
+    marker: PhantomData<E>,
+}
+
+impl<E> EventReader<E> {
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+}
+
+impl<E> Clone for EventReader<E> {
+    fn clone(&self) -> Self {
+        EventReader {
+            id: self.id,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E> Copy for EventReader<E> {}
+
+struct EventQueue<E> {
+    // events[0] is the event numbered `first_event`
+    events: VecDeque<E>,
+    first_event: usize,
+    event_count: usize,
+    // event count at the previous update, older events are dropped at the next one
+    last_update_count: usize,
+    // next event each reader will see, None for removed readers
+    cursors: Vec<Option<usize>>,
+}
+
+/// Queue of events of type E. Events can be sent and read through a shared reference,
+/// so systems can use it during `World::process`. An event is dropped at the first
+/// `update` after every reader has read it, and at the latest at the second `update`
+/// after it was sent.
+pub struct Events<E> {
+    queue: RefCell<EventQueue<E>>,
+}
+
+impl<E> Events<E> {
+    pub fn new() -> Self {
+        Events {
+            queue: RefCell::new(EventQueue {
+                events: VecDeque::new(),
+                first_event: 0,
+                event_count: 0,
+                last_update_count: 0,
+                cursors: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn send(&self, event: E) {
+        let mut queue = self.queue.borrow_mut();
+        queue.events.push_back(event);
+        queue.event_count += 1;
+    }
+
+    /// Creates a reader that will see the events sent from now on.
+    pub fn reader(&self) -> EventReader<E> {
+        let mut queue = self.queue.borrow_mut();
+        let cursor = queue.event_count;
+        queue.cursors.push(Some(cursor));
+        EventReader {
+            id: queue.cursors.len() - 1,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn remove_reader(&self, reader: EventReader<E>) {
+        let mut queue = self.queue.borrow_mut();
+        match queue.cursors.get_mut(reader.id) {
+            Some(cursor) => {
+                *cursor = None;
+            },
+            None => {}
+        }
+    }
+
+    /// Returns the events the reader hasn't seen yet, oldest first, and moves its cursor past them.
+    pub fn read(&self, reader: &EventReader<E>) -> Vec<E> where E: Clone {
+        let mut queue = self.queue.borrow_mut();
+        let cursor = match queue.cursors.get(reader.id) {
+            Some(&Some(cursor)) => cursor,
+            _ => return Vec::new()
+        };
+        // events dropped before the reader got to them are lost
+        let start = if cursor > queue.first_event { cursor - queue.first_event } else { 0 };
+        let events = queue.events.iter().skip(start).cloned().collect();
+        queue.cursors[reader.id] = Some(queue.event_count);
+        events
+    }
+
+    /// Number of events the reader hasn't seen yet.
+    pub fn len(&self, reader: &EventReader<E>) -> usize {
+        let queue = self.queue.borrow();
+        match queue.cursors.get(reader.id) {
+            Some(&Some(cursor)) => queue.event_count - cursor.max(queue.first_event),
+            _ => 0
+        }
+    }
+
+    pub fn is_empty(&self, reader: &EventReader<E>) -> bool {
+        self.len(reader) == 0
+    }
+
+    /// Drops the events every reader has seen and the ones sent before the previous update.
+    pub fn update(&mut self) {
+        let queue = self.queue.get_mut();
+        let mut keep_from = queue.event_count;
+        for cursor in &queue.cursors {
+            match *cursor {
+                Some(cursor) if cursor < keep_from => {
+                    keep_from = cursor;
+                },
+                _ => {}
+            }
+        }
+        if keep_from < queue.last_update_count {
+            keep_from = queue.last_update_count;
+        }
+        while queue.first_event < keep_from {
+            queue.events.pop_front();
+            queue.first_event += 1;
+        }
+        queue.last_update_count = queue.event_count;
+    }
+
+    pub fn clear(&mut self) {
+        let queue = self.queue.get_mut();
+        queue.events.clear();
+        queue.first_event = queue.event_count;
+    }
+}
+
+/// Type erased access to an event channel so the World can update all of them at once.
+pub trait AnyEvents {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<E: Any> AnyEvents for Events<E> {
+    fn update(&mut self) {
+        Events::update(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::World;
+
+    #[test]
+    fn readers_have_their_own_cursor() {
+        let events: Events<u32> = Events::new();
+        let a = events.reader();
+        events.send(1);
+        let b = events.reader();
+        events.send(2);
+        assert_eq!(events.read(&a), vec![1, 2]);
+        assert!(events.is_empty(&a));
+        assert_eq!(events.len(&b), 1);
+        assert_eq!(events.read(&b), vec![2]);
+    }
+
+    #[test]
+    fn events_live_until_read_or_two_updates_old() {
+        let mut events: Events<u32> = Events::new();
+        let a = events.reader();
+        let b = events.reader();
+        events.send(1);
+        assert_eq!(events.read(&a), vec![1]);
+        events.update();
+        events.send(2);
+        // b hasn't read 1 yet, so it survived the update
+        assert_eq!(events.read(&b), vec![1, 2]);
+        events.update();
+        events.send(3);
+        events.update();
+        // 2 is two updates old now, a never read it
+        assert_eq!(events.read(&a), vec![3]);
+        events.remove_reader(b);
+        events.update();
+        events.update();
+        assert!(events.is_empty(&a));
+    }
+
+    #[test]
+    fn readers_dont_miss_events_across_world_updates() {
+        let mut world: World<()> = World::new();
+        world.add_event::<u32>();
+        let reader = world.event_reader::<u32>();
+        world.send_event(1u32);
+        world.update_events();
+        world.send_event(2u32);
+        assert_eq!(world.read_events(&reader), vec![1, 2]);
+        world.send_event(3u32);
+        world.update_events();
+        world.update_events();
+        assert_eq!(world.read_events(&reader), Vec::<u32>::new());
+        world.send_event(4u32);
+        world.update_events();
+        assert_eq!(world.read_events(&reader), vec![4]);
+    }
+}
@@ -15,7 +15,7 @@ impl EverythingRenderer {
     }
 }
 
-impl<T> Renderer<T> for EverythingRenderer {
+impl<T: 'static> Renderer<T> for EverythingRenderer {
     fn render_end <'sb>(&self, scene: &Scene<T>, viewport: Rectangle, spritebatch: &'sb mut SpriteBatch) {
         scene.render_entities();
         spritebatch.end(viewport);
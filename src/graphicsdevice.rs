@@ -245,6 +245,19 @@ impl GraphicsDevice {
         }
     }
 
+    pub fn set_uniform_float(shader: &Shader, name: &str, value: f32) {
+        unsafe {
+            let c_str = CString::new(name.as_bytes()).unwrap();
+            let mut id: GLint = 0;
+            id = gl::GetUniformLocation(shader.program, c_str.as_ptr());
+            if id == -1 {
+                Log::error(format!("Cannot find uniform called {}", name).as_str());
+                return;
+            }
+            gl::Uniform1f(id, value);
+        }
+    }
+
     pub fn set_uniform_float2(shader: &Shader, name: &str, value1: f32, value2: f32) {
         unsafe {
             let c_str = CString::new(name.as_bytes()).unwrap();
@@ -258,6 +271,19 @@ impl GraphicsDevice {
         }
     }
 
+    pub fn set_uniform_float4(shader: &Shader, name: &str, value1: f32, value2: f32, value3: f32, value4: f32) {
+        unsafe {
+            let c_str = CString::new(name.as_bytes()).unwrap();
+            let mut id: GLint = 0;
+            id = gl::GetUniformLocation(shader.program, c_str.as_ptr());
+            if id == -1 {
+                Log::error(format!("Cannot find uniform called {}", name).as_str());
+                return;
+            }
+            gl::Uniform4f(id, value1, value2, value3, value4);
+        }
+    }
+
     pub fn set_uniform_mat4(shader: &Shader, name: &str, mat: Matrix4<f32>) {
         unsafe {
             let c_str = CString::new(name.as_bytes()).unwrap();
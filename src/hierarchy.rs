@@ -0,0 +1,142 @@
+use component::Component;
+use entity::Entity;
+use std::collections::HashMap;
+use std::vec::Vec;
+
+/// The entity this one is attached to. Managed by `World::set_parent` and `World::remove_parent`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParentComponent {
+    parent: Entity,
+}
+
+impl Component for ParentComponent {
+    type Storage = HashMap<usize, Self>;
+}
+
+impl ParentComponent {
+    pub fn new(parent: Entity) -> Self {
+        ParentComponent {
+            parent: parent,
+        }
+    }
+
+    pub fn get_parent(&self) -> Entity {
+        self.parent
+    }
+}
+
+/// The entities attached to this one, in the order they were attached.
+/// Managed by `World::set_parent` and `World::remove_parent`.
+#[derive(Clone)]
+pub struct ChildrenComponent {
+    children: Vec<Entity>,
+}
+
+impl Component for ChildrenComponent {
+    type Storage = HashMap<usize, Self>;
+}
+
+impl ChildrenComponent {
+    pub fn new() -> Self {
+        ChildrenComponent {
+            children: Vec::new(),
+        }
+    }
+
+    pub fn get_children(&self) -> &[Entity] {
+        &self.children
+    }
+
+    pub fn add_child(&mut self, child: Entity) {
+        if !self.children.contains(&child) {
+            self.children.push(child);
+        }
+    }
+
+    pub fn remove_child(&mut self, child: Entity) {
+        self.children.retain(|c| *c != child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transformcomponent::{GlobalTransformComponent, TransformComponent};
+    use world::World;
+
+    fn spawn_at<T: 'static>(world: &mut World<T>, x: f32, y: f32) -> Entity {
+        let entity = world.create_entity();
+        let mut transform = TransformComponent::new();
+        transform.set_position(x, y);
+        world.add_component_to_entity(entity, transform);
+        entity
+    }
+
+    fn global_position<T: 'static>(world: &World<T>, entity: Entity) -> (f32, f32) {
+        let position = *world.get_component_for_entity::<GlobalTransformComponent>(entity).unwrap().get_position();
+        ((position.x * 1000.0).round() / 1000.0, (position.y * 1000.0).round() / 1000.0)
+    }
+
+    #[test]
+    fn children_follow_their_parent() {
+        let mut world: World<()> = World::new();
+        world.register_component_with_storage::<TransformComponent>();
+        let parent = spawn_at(&mut world, 10.0, 0.0);
+        world.with_component_mut::<TransformComponent, _, _>(parent, |transform| {
+            transform.set_rotation(::std::f32::consts::PI / 2.0);
+            transform.set_scale(2.0, 2.0);
+        });
+        let child = spawn_at(&mut world, 1.0, 0.0);
+        let grandchild = spawn_at(&mut world, 1.0, 0.0);
+        world.set_parent(child, parent);
+        world.set_parent(grandchild, child);
+        world.propagate_transforms();
+        assert_eq!(global_position(&world, child), (10.0, 2.0));
+        assert_eq!(global_position(&world, grandchild), (10.0, 4.0));
+        // nothing moved, so nothing changes
+        world.clear_trackers();
+        world.propagate_transforms();
+        assert_eq!(world.query::<&GlobalTransformComponent>().changed::<GlobalTransformComponent>().count(), 0);
+    }
+
+    #[test]
+    fn reparenting_moves_the_whole_branch() {
+        let mut world: World<()> = World::new();
+        world.register_component_with_storage::<TransformComponent>();
+        let first = spawn_at(&mut world, 10.0, 0.0);
+        let second = spawn_at(&mut world, 0.0, 50.0);
+        let child = spawn_at(&mut world, 1.0, 1.0);
+        let grandchild = spawn_at(&mut world, 1.0, 0.0);
+        world.set_parent(child, first);
+        world.set_parent(grandchild, child);
+        world.propagate_transforms();
+        assert_eq!(global_position(&world, grandchild), (12.0, 1.0));
+        world.set_parent(child, second);
+        assert_eq!(world.get_parent(child), Some(second));
+        assert!(world.get_children(first).is_empty());
+        assert_eq!(world.get_children(second), vec![child]);
+        world.propagate_transforms();
+        assert_eq!(global_position(&world, child), (1.0, 51.0));
+        assert_eq!(global_position(&world, grandchild), (2.0, 51.0));
+        world.remove_parent(child);
+        world.propagate_transforms();
+        assert_eq!(global_position(&world, grandchild), (2.0, 1.0));
+    }
+
+    #[test]
+    fn cycles_are_refused_and_destroying_a_parent_destroys_its_children() {
+        let mut world: World<()> = World::new();
+        world.register_component_with_storage::<TransformComponent>();
+        let parent = spawn_at(&mut world, 0.0, 0.0);
+        let child = spawn_at(&mut world, 0.0, 0.0);
+        let grandchild = spawn_at(&mut world, 0.0, 0.0);
+        world.set_parent(child, parent);
+        world.set_parent(grandchild, child);
+        world.set_parent(parent, grandchild);
+        assert_eq!(world.get_parent(parent), None);
+        world.destroy_entity(child);
+        assert!(world.is_alive(parent));
+        assert!(!world.is_alive(grandchild));
+        assert!(world.get_children(parent).is_empty());
+    }
+}
@@ -2,13 +2,18 @@ extern crate cgmath;
 
 use color::Color;
 use component::{Component};
+use storage::DenseVecStorage;
 use rectangle::Rectangle;
 use texture::Texture;
 use spritebatch::SpriteBatch;
 use subtexture::Subtexture;
-use entity::Entity;
+use transformcomponent::GlobalTransformComponent;
+use texturemanager::TextureManager;
+use prefab;
+use prefab::PrefabComponent;
+use world::World;
+use serde_json::Value;
 use self::cgmath::Vector2;
-use std::collections::HashMap;
 use std::rc::Rc;
 use std::option::Option;
 use log::Log;
@@ -25,7 +30,31 @@ pub struct ImageComponent {
 }
 
 impl Component for ImageComponent {
-    type Storage = HashMap<usize, Self>;
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// `{"texture": id}`, the id of a texture loaded in the TextureManager resource.
+impl PrefabComponent for ImageComponent {
+    fn get_prefab_name() -> &'static str {
+        "image"
+    }
+
+    fn from_prefab<T: 'static>(world: &World<T>, value: &Value) -> Option<Self> {
+        let id = prefab::prefab_str(value, "texture")?;
+        let texture = match world.resource::<TextureManager>() {
+            Some(tm) => tm.find(id),
+            None => None
+        };
+        match texture {
+            Some(texture) => {
+                return Some(ImageComponent::with_texture(texture));
+            },
+            None => {
+                Log::error(&format!("Unknown texture {}", id));
+                return None;
+            }
+        }
+    }
 }
 
 impl ImageComponent {
@@ -49,12 +78,18 @@ impl ImageComponent {
         ic
     }
 
-    pub fn render(&self, entity: Option<&Entity>, spritebatch: &mut SpriteBatch) {
+    pub fn render(&self, transform: Option<&GlobalTransformComponent>, spritebatch: &mut SpriteBatch) {
         Log::info("Render called");
-        let render_pos = self.render_position(entity);
-        let scaled_vec = self.scale * self.zoom;
+        let render_pos = self.render_position(transform);
+        let (rotation, scaled_vec) = match transform {
+            Some(transform) => {
+                let scale = transform.get_scale();
+                (self.rotation + transform.get_rotation(), Vector2::new(self.scale.x * scale.x, self.scale.y * scale.y) * self.zoom)
+            },
+            None => (self.rotation, self.scale * self.zoom)
+        };
         let t = self.texture.as_ref().unwrap().clone();
-        spritebatch.draw_vector_scale(t, Some(render_pos), Some(self.clip_rect), self.color, self.rotation,
+        spritebatch.draw_vector_scale(t, Some(render_pos), Some(self.clip_rect), self.color, rotation,
         self.origin, scaled_vec, 0.0);
     }
 
@@ -90,15 +125,14 @@ impl ImageComponent {
         }
     }
 
-    pub fn render_position(&self, entity: Option<&Entity>) -> Vector2<f32> {
-        match entity {
-            Some(_) => {
-                //return entity.unwrap().position + self.position;
-                // TODO: grab the position from the TransformComponent?
-                return Vector2::new(0.0, 0.0);
+    /// Position of the component in world space, given the transform of its entity.
+    pub fn render_position(&self, transform: Option<&GlobalTransformComponent>) -> Vector2<f32> {
+        match transform {
+            Some(transform) => {
+                return transform.transform_point(self.position);
             },
             None => {
-                return Vector2::new(0.0, 0.0);
+                return self.position;
             }
         }
     }
@@ -50,19 +50,33 @@ pub mod spritebatcher;
 pub mod spritefont;
 pub mod texturemanager;
 pub mod transformcomponent;
+pub mod depthcomponent;
 pub mod camera;
 pub mod utils;
 pub mod viewportadapter;
 pub mod entity;
 pub mod component;
+pub mod storage;
 pub mod world;
+pub mod query;
+pub mod commandbuffer;
+pub mod resources;
+pub mod events;
+pub mod hierarchy;
+pub mod taglists;
+pub mod serialization;
+pub mod prefab;
 pub mod scene;
+pub mod scenemanager;
 pub mod collider;
 pub mod colliderlist;
+pub mod collidercomponent;
+pub mod spatialhash;
 pub mod subtexture;
 pub mod imagecomponent;
 pub mod renderer;
 pub mod everythingrenderer;
+pub mod tagrenderer;
 pub mod debugnamecomponentmanager;
 pub mod timer;
 pub mod atlas;
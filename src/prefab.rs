@@ -0,0 +1,394 @@
+extern crate cgmath;
+
+use component::Component;
+use entity::Entity;
+use world::World;
+use log::Log;
+use serde_json;
+use serde_json::{Map, Value};
+use self::cgmath::Vector2;
+use std::collections::HashMap;
+use std::string::String;
+use std::vec::Vec;
+
+/// Component that can be described in a prefab file.
+pub trait PrefabComponent: Component {
+    /// Key of the component in the `components` object of a prefab.
+    fn get_prefab_name() -> &'static str;
+
+    /// Builds the component from its description. The world gives access to resources
+    /// like the TextureManager to resolve texture and atlas names.
+    fn from_prefab<T: 'static>(world: &World<T>, value: &Value) -> Option<Self>;
+}
+
+/// Template of an entity: its name, tags, components keyed by prefab name and children.
+/// A prefab inheriting from another one starts from a copy of it: component descriptions
+/// are merged field by field, tags and children are added to the inherited ones.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Prefab {
+    #[serde(default)]
+    pub inherits: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<u32>,
+    #[serde(default)]
+    pub components: Map<String, Value>,
+    #[serde(default)]
+    pub children: Vec<Prefab>,
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Prefab {
+            inherits: None,
+            name: None,
+            tags: Vec::new(),
+            components: Map::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// A prefab inheriting from the given one, to override some of its values.
+    pub fn inheriting(name: &str) -> Self {
+        let mut prefab = Prefab::new();
+        prefab.inherits = Some(String::from(name));
+        prefab
+    }
+
+    pub fn with_component(mut self, name: &str, value: Value) -> Self {
+        self.components.insert(String::from(name), value);
+        self
+    }
+}
+
+/// Builds one component type from its description, see `World::register_prefab_component`.
+pub struct PrefabLoader<T> {
+    name: &'static str,
+    load: fn(&mut World<T>, Entity, &Value) -> bool,
+}
+
+impl<T: 'static> PrefabLoader<T> {
+    pub fn of<C: PrefabComponent>() -> Self {
+        PrefabLoader {
+            name: C::get_prefab_name(),
+            load: load_prefab_component::<T, C>,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Adds the described component to the entity. Returns false if the description is invalid.
+    pub fn load(&self, world: &mut World<T>, entity: Entity, value: &Value) -> bool {
+        (self.load)(world, entity, value)
+    }
+}
+
+impl<T> Clone for PrefabLoader<T> {
+    fn clone(&self) -> Self {
+        PrefabLoader {
+            name: self.name,
+            load: self.load,
+        }
+    }
+}
+
+fn load_prefab_component<T: 'static, C: PrefabComponent>(world: &mut World<T>, entity: Entity, value: &Value) -> bool {
+    match C::from_prefab(world, value) {
+        Some(component) => {
+            world.add_component_to_entity(entity, component);
+            true
+        },
+        None => {
+            Log::error(&format!("Invalid {} in prefab", C::get_prefab_name()));
+            false
+        }
+    }
+}
+
+/// The prefabs known to a World by name and the component types they can use.
+pub struct PrefabLibrary<T> {
+    prefabs: HashMap<String, Prefab>,
+    loaders: Vec<PrefabLoader<T>>,
+}
+
+impl<T: 'static> PrefabLibrary<T> {
+    pub fn new() -> Self {
+        PrefabLibrary {
+            prefabs: HashMap::new(),
+            loaders: Vec::new(),
+        }
+    }
+
+    pub fn register_component<C: PrefabComponent>(&mut self) {
+        let name = C::get_prefab_name();
+        if self.loaders.iter().any(|loader| loader.get_name() == name) {
+            Log::warning(&format!("A prefab component named {} is already registered", name));
+            return;
+        }
+        self.loaders.push(PrefabLoader::of::<C>());
+    }
+
+    pub fn get_loaders(&self) -> &[PrefabLoader<T>] {
+        &self.loaders
+    }
+
+    /// Adds the prefab, replacing the one with the same name.
+    pub fn add(&mut self, name: &str, prefab: Prefab) {
+        self.prefabs.insert(String::from(name), prefab);
+    }
+
+    /// Parses the prefab from JSON and adds it. Returns false if it isn't valid.
+    pub fn load(&mut self, name: &str, json: &str) -> bool {
+        match serde_json::from_str::<Prefab>(json) {
+            Ok(prefab) => {
+                self.add(name, prefab);
+                true
+            },
+            Err(e) => {
+                Log::error(&format!("Cannot load prefab {}: {}", name, e));
+                false
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.prefabs.contains_key(name)
+    }
+
+    /// Flattens the inheritance chain of the prefab and of its children.
+    pub fn resolve(&self, prefab: &Prefab) -> Option<Prefab> {
+        self.resolve_inheriting(prefab, &mut Vec::new())
+    }
+
+    fn resolve_inheriting(&self, prefab: &Prefab, inherited: &mut Vec<String>) -> Option<Prefab> {
+        let mut resolved = match prefab.inherits {
+            Some(ref name) => {
+                if inherited.contains(name) {
+                    Log::error(&format!("Prefab {} inherits from itself", name));
+                    return None;
+                }
+                let base = match self.prefabs.get(name) {
+                    Some(base) => base,
+                    None => {
+                        Log::error(&format!("Unknown prefab {}", name));
+                        return None;
+                    }
+                };
+                inherited.push(name.clone());
+                let resolved = self.resolve_inheriting(base, inherited);
+                inherited.pop();
+                resolved?
+            },
+            None => Prefab::new()
+        };
+        if prefab.name.is_some() {
+            resolved.name = prefab.name.clone();
+        }
+        for tag in &prefab.tags {
+            if !resolved.tags.contains(tag) {
+                resolved.tags.push(*tag);
+            }
+        }
+        for (name, value) in &prefab.components {
+            match resolved.components.get_mut(name) {
+                Some(base) => {
+                    merge_values(base, value);
+                    continue;
+                },
+                None => {}
+            }
+            resolved.components.insert(name.clone(), value.clone());
+        }
+        for child in &prefab.children {
+            // children start a chain of their own, they may use the prefab they belong to
+            let child = self.resolve_inheriting(child, &mut Vec::new())?;
+            resolved.children.push(child);
+        }
+        Some(resolved)
+    }
+}
+
+/// Overwrites `base` with `value`, going into the objects they both have so that
+/// only the fields given in `value` change.
+pub fn merge_values(base: &mut Value, value: &Value) {
+    match (base, value) {
+        (&mut Value::Object(ref mut base), &Value::Object(ref value)) => {
+            for (key, value) in value {
+                match base.get_mut(key) {
+                    Some(base) => {
+                        merge_values(base, value);
+                        continue;
+                    },
+                    None => {}
+                }
+                base.insert(key.clone(), value.clone());
+            }
+        },
+        (base, value) => {
+            *base = value.clone();
+        }
+    }
+}
+
+/// Creates the entity described by a resolved prefab, along with its children.
+/// Returns None, destroying what was created, if one of the components can't be loaded.
+pub fn instantiate<T: 'static>(world: &mut World<T>, prefab: &Prefab, loaders: &[PrefabLoader<T>]) -> Option<Entity> {
+    let entity = world.create_entity();
+    if let Some(ref name) = prefab.name {
+        world.set_name(entity, name);
+    }
+    for tag in &prefab.tags {
+        world.add_tag(entity, *tag);
+    }
+    for (name, value) in &prefab.components {
+        match loaders.iter().find(|loader| loader.get_name() == name.as_str()) {
+            Some(loader) => {
+                if !loader.load(world, entity, value) {
+                    Log::warning(&format!("Cannot instantiate prefab {}: invalid component {}",
+                        prefab.name.as_ref().map(|name| name.as_str()).unwrap_or("<unnamed>"), name));
+                    world.destroy_entity(entity);
+                    return None;
+                }
+            },
+            None => {
+                Log::warning(&format!("Skipping unknown prefab component {}", name));
+            }
+        }
+    }
+    for child in &prefab.children {
+        match instantiate(world, child, loaders) {
+            Some(child) => {
+                world.set_parent(child, entity);
+            },
+            None => {
+                // destroys the children created so far too
+                world.destroy_entity(entity);
+                return None;
+            }
+        }
+    }
+    Some(entity)
+}
+
+pub fn prefab_f32(value: &Value, key: &str) -> Option<f32> {
+    value.get(key).and_then(|v| v.as_f64()).map(|v| v as f32)
+}
+
+pub fn prefab_str<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(|v| v.as_str())
+}
+
+/// Reads `{"x": .., "y": ..}`, a missing coordinate being 0.
+pub fn prefab_vector(value: &Value, key: &str) -> Option<Vector2<f32>> {
+    match value.get(key) {
+        Some(vector) if vector.is_object() => {
+            Some(Vector2::new(prefab_f32(vector, "x").unwrap_or(0.0), prefab_f32(vector, "y").unwrap_or(0.0)))
+        },
+        _ => None
+    }
+}
+
+/// Components overriding the position of the spawned entity.
+pub fn position_override(x: f32, y: f32) -> Value {
+    let mut position = Map::new();
+    position.insert(String::from("x"), Value::from(x as f64));
+    position.insert(String::from("y"), Value::from(y as f64));
+    let mut transform = Map::new();
+    transform.insert(String::from("position"), Value::Object(position));
+    Value::Object(transform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_values, Prefab};
+    use serde_json;
+    use serde_json::Value;
+    use transformcomponent::TransformComponent;
+    use world::World;
+
+    fn value(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn world_with_prefabs() -> World<()> {
+        let mut world: World<()> = World::new();
+        world.register_prefab_component::<TransformComponent>();
+        assert!(world.load_prefab("base", r#"{
+            "name": "base",
+            "tags": [1],
+            "components": {"transform": {"position": {"x": 1, "y": 2}, "rotation": 0.5}}
+        }"#));
+        assert!(world.load_prefab("derived", r#"{
+            "inherits": "base",
+            "name": "derived",
+            "tags": [2],
+            "components": {"transform": {"position": {"y": 5}}},
+            "children": [{"inherits": "base", "name": "child"}]
+        }"#));
+        world
+    }
+
+    #[test]
+    fn merging_only_overrides_the_given_fields() {
+        let mut base = value(r#"{"position": {"x": 1, "y": 2}, "rotation": 0.5}"#);
+        merge_values(&mut base, &value(r#"{"position": {"y": 5}, "scale": 2}"#));
+        assert_eq!(base, value(r#"{"position": {"x": 1, "y": 5}, "rotation": 0.5, "scale": 2}"#));
+        merge_values(&mut base, &value(r#"{"position": 3}"#));
+        assert_eq!(base["position"], value("3"));
+    }
+
+    #[test]
+    fn prefabs_inherit_components_tags_and_children() {
+        let mut world = world_with_prefabs();
+        let entity = world.spawn_prefab("derived").unwrap();
+        assert_eq!(world.get_name(entity), Some("derived"));
+        assert!(world.has_tag(entity, 1) && world.has_tag(entity, 2));
+        {
+            let transform = world.get_component_for_entity::<TransformComponent>(entity).unwrap();
+            assert_eq!((transform.get_position().x, transform.get_position().y), (1.0, 5.0));
+            assert_eq!(transform.get_rotation(), 0.5);
+        }
+        let children = world.get_children(entity);
+        assert_eq!(children.len(), 1);
+        assert_eq!(world.get_name(children[0]), Some("child"));
+        assert_eq!(world.get_component_for_entity::<TransformComponent>(children[0]).unwrap().get_position().y, 2.0);
+    }
+
+    #[test]
+    fn inheritance_cycles_are_refused() {
+        let mut world: World<()> = World::new();
+        world.add_prefab("a", Prefab::inheriting("b"));
+        world.add_prefab("b", Prefab::inheriting("a"));
+        assert!(world.spawn_prefab("a").is_none());
+        assert!(world.spawn_prefab("unknown").is_none());
+        // a child may use the prefab it belongs to as a base without forming a cycle
+        let mut parent = Prefab::new();
+        parent.children.push(Prefab::inheriting("leaf"));
+        world.add_prefab("leaf", Prefab::new());
+        world.add_prefab("parent", parent);
+        assert!(world.spawn_prefab("parent").is_some());
+    }
+
+    #[test]
+    fn invalid_components_destroy_the_partial_entity() {
+        let mut world = world_with_prefabs();
+        let overrides = value(r#"{"transform": 3}"#);
+        assert!(world.spawn_prefab_with("derived", overrides).is_none());
+        assert!(world.find_by_name("derived").is_none());
+
+        let mut child = Prefab::inheriting("base").with_component("transform", value("false"));
+        child.name = Some(String::from("broken"));
+        let mut parent = Prefab::inheriting("base");
+        parent.children.push(child);
+        assert!(world.spawn_prefab_from(&parent).is_none());
+        assert!(world.find_by_name("base").is_none());
+        assert!(world.find_by_name("broken").is_none());
+    }
+}
@@ -0,0 +1,568 @@
+use component::{AnyStorage, Component, ComponentStorage, GenericStorage};
+use entity::{Entity, EntityData};
+use taglists::TagFilter;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::vec::Vec;
+
+/// Read only element of a query: `&C`, `Option<&C>` or a tuple of those.
+/// Every element takes one or more storage slots, in the order they appear in the query.
+pub trait Fetch<'a> {
+    type Item;
+    fn slots() -> usize;
+    fn add_types(types: &mut Vec<TypeId>);
+    fn add_required(required: &mut Vec<TypeId>);
+    fn fetch(storages: &[Option<&'a Box<dyn AnyStorage>>], index: usize) -> Option<Self::Item>;
+}
+
+/// Element of a query that can also hand out mutable references:
+/// `&C`, `&mut C`, `Option<&C>`, `Option<&mut C>` or a tuple of those.
+pub trait FetchMut<'a> {
+    type Item;
+    fn slots() -> usize;
+    fn add_types(types: &mut Vec<TypeId>);
+    fn add_required(required: &mut Vec<TypeId>);
+    /// `tick` is stamped on the components borrowed mutably.
+    fn fetch<'w>(storages: &'a mut [Option<&'w mut Box<dyn AnyStorage>>], index: usize, tick: u32) -> Option<Self::Item>;
+}
+
+fn storage_ref<'a, C: Component>(storage: Option<&'a Box<dyn AnyStorage>>) -> Option<&'a C::Storage> {
+    match storage {
+        Some(storage) => {
+            storage.as_any().downcast_ref::<ComponentStorage<C>>().map(|s| s.get_storage())
+        },
+        None => None
+    }
+}
+
+fn storage_mut<'a, 'w, C: Component>(storage: &'a mut Option<&'w mut Box<dyn AnyStorage>>) -> Option<&'a mut ComponentStorage<C>> {
+    match storage.as_mut() {
+        Some(storage) => {
+            storage.as_any_mut().downcast_mut::<ComponentStorage<C>>()
+        },
+        None => None
+    }
+}
+
+impl<'a, 'b, C: Component> Fetch<'a> for &'b C {
+    type Item = &'a C;
+
+    fn slots() -> usize {
+        1
+    }
+
+    fn add_types(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<C>());
+    }
+
+    fn add_required(required: &mut Vec<TypeId>) {
+        required.push(TypeId::of::<C>());
+    }
+
+    fn fetch(storages: &[Option<&'a Box<dyn AnyStorage>>], index: usize) -> Option<Self::Item> {
+        storage_ref::<C>(storages[0])?.get(index)
+    }
+}
+
+impl<'a, 'b, C: Component> Fetch<'a> for Option<&'b C> {
+    type Item = Option<&'a C>;
+
+    fn slots() -> usize {
+        1
+    }
+
+    fn add_types(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<C>());
+    }
+
+    fn add_required(_required: &mut Vec<TypeId>) {
+    }
+
+    fn fetch(storages: &[Option<&'a Box<dyn AnyStorage>>], index: usize) -> Option<Self::Item> {
+        Some(storage_ref::<C>(storages[0]).and_then(|s| s.get(index)))
+    }
+}
+
+impl<'a, 'b, C: Component> FetchMut<'a> for &'b C {
+    type Item = &'a C;
+
+    fn slots() -> usize {
+        1
+    }
+
+    fn add_types(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<C>());
+    }
+
+    fn add_required(required: &mut Vec<TypeId>) {
+        required.push(TypeId::of::<C>());
+    }
+
+    fn fetch<'w>(storages: &'a mut [Option<&'w mut Box<dyn AnyStorage>>], index: usize, _tick: u32) -> Option<Self::Item> {
+        let storage: &'a ComponentStorage<C> = storage_mut::<C>(&mut storages[0])?;
+        storage.get(index)
+    }
+}
+
+impl<'a, 'b, C: Component> FetchMut<'a> for &'b mut C {
+    type Item = &'a mut C;
+
+    fn slots() -> usize {
+        1
+    }
+
+    fn add_types(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<C>());
+    }
+
+    fn add_required(required: &mut Vec<TypeId>) {
+        required.push(TypeId::of::<C>());
+    }
+
+    fn fetch<'w>(storages: &'a mut [Option<&'w mut Box<dyn AnyStorage>>], index: usize, tick: u32) -> Option<Self::Item> {
+        storage_mut::<C>(&mut storages[0])?.get_mut(index, tick)
+    }
+}
+
+impl<'a, 'b, C: Component> FetchMut<'a> for Option<&'b C> {
+    type Item = Option<&'a C>;
+
+    fn slots() -> usize {
+        1
+    }
+
+    fn add_types(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<C>());
+    }
+
+    fn add_required(_required: &mut Vec<TypeId>) {
+    }
+
+    fn fetch<'w>(storages: &'a mut [Option<&'w mut Box<dyn AnyStorage>>], index: usize, _tick: u32) -> Option<Self::Item> {
+        match storage_mut::<C>(&mut storages[0]) {
+            Some(storage) => {
+                let storage: &'a ComponentStorage<C> = storage;
+                Some(storage.get(index))
+            },
+            None => Some(None)
+        }
+    }
+}
+
+impl<'a, 'b, C: Component> FetchMut<'a> for Option<&'b mut C> {
+    type Item = Option<&'a mut C>;
+
+    fn slots() -> usize {
+        1
+    }
+
+    fn add_types(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<C>());
+    }
+
+    fn add_required(_required: &mut Vec<TypeId>) {
+    }
+
+    fn fetch<'w>(storages: &'a mut [Option<&'w mut Box<dyn AnyStorage>>], index: usize, tick: u32) -> Option<Self::Item> {
+        Some(storage_mut::<C>(&mut storages[0]).and_then(|s| s.get_mut(index, tick)))
+    }
+}
+
+macro_rules! impl_fetch_tuple {
+    ($($name:ident),+) => {
+        impl<'a, $($name: Fetch<'a>),+> Fetch<'a> for ($($name,)+) {
+            type Item = ($($name::Item,)+);
+
+            fn slots() -> usize {
+                0 $(+ $name::slots())+
+            }
+
+            fn add_types(types: &mut Vec<TypeId>) {
+                $($name::add_types(types);)+
+            }
+
+            fn add_required(required: &mut Vec<TypeId>) {
+                $($name::add_required(required);)+
+            }
+
+            #[allow(non_snake_case, unused_assignments)]
+            fn fetch(storages: &[Option<&'a Box<dyn AnyStorage>>], index: usize) -> Option<Self::Item> {
+                let mut offset = 0;
+                $(
+                    let $name = $name::fetch(&storages[offset..offset + $name::slots()], index)?;
+                    offset += $name::slots();
+                )+
+                Some(($($name,)+))
+            }
+        }
+
+        impl<'a, $($name: FetchMut<'a>),+> FetchMut<'a> for ($($name,)+) {
+            type Item = ($($name::Item,)+);
+
+            fn slots() -> usize {
+                0 $(+ $name::slots())+
+            }
+
+            fn add_types(types: &mut Vec<TypeId>) {
+                $($name::add_types(types);)+
+            }
+
+            fn add_required(required: &mut Vec<TypeId>) {
+                $($name::add_required(required);)+
+            }
+
+            #[allow(non_snake_case, unused_variables)]
+            fn fetch<'w>(storages: &'a mut [Option<&'w mut Box<dyn AnyStorage>>], index: usize, tick: u32) -> Option<Self::Item> {
+                let rest = storages;
+                $(
+                    let (head, rest) = rest.split_at_mut($name::slots());
+                    let $name = $name::fetch(head, index, tick)?;
+                )+
+                Some(($($name,)+))
+            }
+        }
+    }
+}
+
+impl_fetch_tuple!(A);
+impl_fetch_tuple!(A, B);
+impl_fetch_tuple!(A, B, C);
+impl_fetch_tuple!(A, B, C, D);
+impl_fetch_tuple!(A, B, C, D, E);
+impl_fetch_tuple!(A, B, C, D, E, F);
+
+/// Filters shared by Query and QueryMut on top of the components they fetch.
+struct Filters {
+    with: Vec<TypeId>,
+    without: Vec<TypeId>,
+    added: Vec<TypeId>,
+    changed: Vec<TypeId>,
+    tags: TagFilter,
+    since: u32,
+}
+
+impl Filters {
+    fn new(since: u32) -> Self {
+        Filters {
+            with: Vec::new(),
+            without: Vec::new(),
+            added: Vec::new(),
+            changed: Vec::new(),
+            tags: TagFilter::new(),
+            since: since,
+        }
+    }
+
+    /// The components an entity must have, given the ones the query fetches.
+    fn required(&self, add_required: fn(&mut Vec<TypeId>)) -> Vec<TypeId> {
+        let mut required = self.with.clone();
+        required.extend(self.added.iter().cloned());
+        required.extend(self.changed.iter().cloned());
+        add_required(&mut required);
+        required
+    }
+}
+
+/// Returns the living entities that have every required component and none of the excluded ones,
+/// and whose components in `filters.added` / `filters.changed` were added / changed after `filters.since`.
+/// The smallest required storage drives the iteration so rare components keep queries cheap.
+fn matching_entities(entities: &[EntityData], components: &HashMap<TypeId, Box<dyn AnyStorage>>,
+                     required: &[TypeId], filters: &Filters) -> Vec<Entity> {
+    let without = &filters.without;
+    let mut storages = Vec::new();
+    for component_type in required {
+        match components.get(component_type) {
+            Some(storage) => storages.push(storage),
+            None => return Vec::new()
+        }
+    }
+    let excluded = without.iter()
+        .filter_map(|component_type| components.get(component_type))
+        .collect::<Vec<_>>();
+
+    let mut indices = match storages.iter().min_by_key(|storage| storage.len()) {
+        Some(driver) => driver.indices(),
+        None => (0..entities.len()).collect(),
+    };
+    indices.sort();
+
+    indices.into_iter()
+        .filter(|index| {
+            match entities.get(*index) {
+                Some(entity_data) => entity_data.is_alive() && filters.tags.matches(entity_data.get_tags()),
+                None => false
+            }
+        })
+        .filter(|index| storages.iter().all(|storage| storage.contains(*index)))
+        .filter(|index| !excluded.iter().any(|storage| storage.contains(*index)))
+        .filter(|index| {
+            filters.added.iter().all(|component_type| components[component_type].is_added(*index, filters.since))
+        })
+        .filter(|index| {
+            filters.changed.iter().all(|component_type| components[component_type].is_changed(*index, filters.since))
+        })
+        .map(|index| Entity::new(index, entities[index].get_generation()))
+        .collect()
+}
+
+/// Read only query over every entity matching `Q`, built through `World::query`.
+pub struct Query<'w, Q> {
+    entities: &'w [EntityData],
+    components: &'w HashMap<TypeId, Box<dyn AnyStorage>>,
+    filters: Filters,
+    phantom: PhantomData<Q>,
+}
+
+impl<'w, Q: Fetch<'w>> Query<'w, Q> {
+    /// `since` is the tick the `added` and `changed` filters compare against.
+    pub fn new(entities: &'w [EntityData], components: &'w HashMap<TypeId, Box<dyn AnyStorage>>, since: u32) -> Self {
+        Query {
+            entities: entities,
+            components: components,
+            filters: Filters::new(since),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Only matches entities that also have a `C`, without borrowing it.
+    pub fn with<C: Component>(mut self) -> Self {
+        self.filters.with.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Skips entities that have a `C`.
+    pub fn without<C: Component>(mut self) -> Self {
+        self.filters.without.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Only matches entities whose `C` has been added since the caller last looked,
+    /// see `World::get_last_run_tick`.
+    pub fn added<C: Component>(mut self) -> Self {
+        self.filters.added.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Only matches entities whose `C` has been added or borrowed mutably since the caller last looked.
+    pub fn changed<C: Component>(mut self) -> Self {
+        self.filters.changed.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Only matches entities having the tag.
+    pub fn with_tag(mut self, tag: u32) -> Self {
+        self.filters.tags = self.filters.tags.with_tag(tag);
+        self
+    }
+
+    /// Skips entities having the tag.
+    pub fn without_tag(mut self, tag: u32) -> Self {
+        self.filters.tags = self.filters.tags.without_tag(tag);
+        self
+    }
+
+    pub fn entities(&self) -> Vec<Entity> {
+        let required = self.filters.required(Q::add_required);
+        matching_entities(self.entities, self.components, &required, &self.filters)
+    }
+
+    pub fn count(&self) -> usize {
+        self.entities().len()
+    }
+
+    pub fn iter(&self) -> QueryIter<'w, Q> {
+        let mut types = Vec::new();
+        Q::add_types(&mut types);
+        let storages = types.iter()
+            .map(|component_type| self.components.get(component_type))
+            .collect();
+        QueryIter {
+            storages: storages,
+            entities: self.entities().into_iter(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+pub struct QueryIter<'w, Q> {
+    storages: Vec<Option<&'w Box<dyn AnyStorage>>>,
+    entities: ::std::vec::IntoIter<Entity>,
+    phantom: PhantomData<Q>,
+}
+
+impl<'w, Q: Fetch<'w>> Iterator for QueryIter<'w, Q> {
+    type Item = (Entity, Q::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entity) = self.entities.next() {
+            match Q::fetch(&self.storages, entity.get_index()) {
+                Some(item) => return Some((entity, item)),
+                None => {}
+            }
+        }
+        None
+    }
+}
+
+/// Query that can write to the components it matches, built through `World::query_mut`.
+/// Mutable references can't outlive a single entity so the results are handed to a closure.
+pub struct QueryMut<'w, Q> {
+    entities: &'w [EntityData],
+    components: &'w mut HashMap<TypeId, Box<dyn AnyStorage>>,
+    filters: Filters,
+    tick: u32,
+    phantom: PhantomData<Q>,
+}
+
+impl<'w, Q> QueryMut<'w, Q> where Q: for<'a> FetchMut<'a> {
+    /// `since` is the tick the `added` and `changed` filters compare against,
+    /// `tick` the one stamped on the components borrowed mutably.
+    pub fn new(entities: &'w [EntityData], components: &'w mut HashMap<TypeId, Box<dyn AnyStorage>>, since: u32, tick: u32) -> Self {
+        QueryMut {
+            entities: entities,
+            components: components,
+            filters: Filters::new(since),
+            tick: tick,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Only matches entities that also have a `C`, without borrowing it.
+    pub fn with<C: Component>(mut self) -> Self {
+        self.filters.with.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Skips entities that have a `C`.
+    pub fn without<C: Component>(mut self) -> Self {
+        self.filters.without.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Only matches entities whose `C` has been added since the caller last looked,
+    /// see `World::get_last_run_tick`.
+    pub fn added<C: Component>(mut self) -> Self {
+        self.filters.added.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Only matches entities whose `C` has been added or borrowed mutably since the caller last looked.
+    pub fn changed<C: Component>(mut self) -> Self {
+        self.filters.changed.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Only matches entities having the tag.
+    pub fn with_tag(mut self, tag: u32) -> Self {
+        self.filters.tags = self.filters.tags.with_tag(tag);
+        self
+    }
+
+    /// Skips entities having the tag.
+    pub fn without_tag(mut self, tag: u32) -> Self {
+        self.filters.tags = self.filters.tags.without_tag(tag);
+        self
+    }
+
+    pub fn entities(&self) -> Vec<Entity> {
+        let required = self.filters.required(<Q as FetchMut>::add_required);
+        matching_entities(self.entities, self.components, &required, &self.filters)
+    }
+
+    pub fn for_each<F>(self, mut f: F) where F: for<'a> FnMut(Entity, <Q as FetchMut<'a>>::Item) {
+        let entities = self.entities();
+        let mut types = Vec::new();
+        Q::add_types(&mut types);
+        for (i, component_type) in types.iter().enumerate() {
+            if types[..i].contains(component_type) {
+                panic!("A component type can only appear once in a mutable query");
+            }
+        }
+
+        let mut available = self.components.iter_mut().collect::<HashMap<_, _>>();
+        let mut storages = types.iter()
+            .map(|component_type| available.remove(component_type))
+            .collect::<Vec<_>>();
+        for entity in entities {
+            match Q::fetch(&mut storages, entity.get_index(), self.tick) {
+                Some(item) => f(entity, item),
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use component::Component;
+    use depthcomponent::DepthComponent;
+    use storage::NullStorage;
+    use transformcomponent::TransformComponent;
+    use world::World;
+
+    #[derive(Default)]
+    struct Marker;
+
+    impl Component for Marker {
+        type Storage = NullStorage<Self>;
+    }
+
+    /// Transforms in a VecStorage, depths in a DenseVecStorage and markers in a NullStorage.
+    fn world() -> World<()> {
+        let mut world = World::new();
+        world.register_component_with_storage::<TransformComponent>();
+        world.register_component_with_storage::<DepthComponent>();
+        world.register_component_with_storage::<Marker>();
+        for i in 0..6 {
+            let entity = world.create_entity();
+            let mut transform = TransformComponent::new();
+            transform.set_position(i as f32, 0.0);
+            world.add_component_to_entity(entity, transform);
+            if i % 2 == 0 {
+                world.add_component_to_entity(entity, DepthComponent::new(i, i as f64));
+            }
+            if i % 3 == 0 {
+                world.add_component_to_entity(entity, Marker);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn joins_over_mixed_storages() {
+        let world = world();
+        let xs: Vec<f32> = world.query::<(&TransformComponent, &DepthComponent)>().iter()
+            .map(|(_, (transform, _))| transform.get_position().x)
+            .collect();
+        assert_eq!(xs, vec![0.0, 2.0, 4.0]);
+        let depths: Vec<i32> = world.query::<(&DepthComponent, &Marker)>().iter()
+            .map(|(_, (depth, _))| depth.get_depth())
+            .collect();
+        assert_eq!(depths, vec![0]);
+        assert_eq!(world.query::<&TransformComponent>().without::<DepthComponent>().count(), 3);
+        assert_eq!(world.query::<&TransformComponent>().with::<Marker>().count(), 2);
+        let missing = world.query::<(&TransformComponent, Option<&DepthComponent>)>().iter()
+            .filter(|&(_, (_, depth))| depth.is_none())
+            .count();
+        assert_eq!(missing, 3);
+    }
+
+    #[test]
+    fn mutable_joins() {
+        let mut world = world();
+        world.query_mut::<(&mut TransformComponent, &DepthComponent)>().for_each(|_, (transform, depth)| {
+            let x = transform.get_position().x;
+            transform.set_position(x, depth.get_depth() as f32 * 10.0);
+        });
+        world.query_mut::<(&mut TransformComponent, Option<&Marker>)>().for_each(|_, (transform, marker)| {
+            if marker.is_some() {
+                transform.set_position(-1.0, -1.0);
+            }
+        });
+        let positions: Vec<(f32, f32)> = world.query::<&TransformComponent>().iter()
+            .map(|(_, transform)| (transform.get_position().x, transform.get_position().y))
+            .collect();
+        assert_eq!(positions, vec![(-1.0, -1.0), (1.0, 0.0), (2.0, 20.0), (-1.0, -1.0), (4.0, 40.0), (5.0, 0.0)]);
+    }
+}
@@ -29,7 +29,7 @@ impl Rectangle {
     }
 
     pub fn get_bottom(&self) -> f32 {
-        self.y + self.w as f32
+        self.y + self.h as f32
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,59 @@
+use std::any::{Any, TypeId};
+use std::boxed::Box;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+
+/// Singletons shared by the systems of a World, one value per type.
+/// Values live in RefCells so systems that only get a `&Scene` can still update them.
+pub struct Resources {
+    resources: HashMap<TypeId, RefCell<Box<dyn Any>>>,
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Resources {
+            resources: HashMap::new(),
+        }
+    }
+
+    /// Stores the resource, replacing the previous value of the same type.
+    pub fn insert<R: Any>(&mut self, resource: R) {
+        self.resources.insert(TypeId::of::<R>(), RefCell::new(Box::new(resource)));
+    }
+
+    pub fn remove<R: Any>(&mut self) -> Option<R> {
+        match self.resources.remove(&TypeId::of::<R>()) {
+            Some(resource) => {
+                match resource.into_inner().downcast::<R>() {
+                    Ok(resource) => Some(*resource),
+                    Err(_) => None
+                }
+            },
+            None => None
+        }
+    }
+
+    pub fn contains<R: Any>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<R>())
+    }
+
+    /// Panics if the resource is already borrowed mutably.
+    pub fn get<R: Any>(&self) -> Option<Ref<R>> {
+        match self.resources.get(&TypeId::of::<R>()) {
+            Some(resource) => {
+                Some(Ref::map(resource.borrow(), |r| r.downcast_ref::<R>().unwrap()))
+            },
+            None => None
+        }
+    }
+
+    /// Panics if the resource is already borrowed.
+    pub fn get_mut<R: Any>(&self) -> Option<RefMut<R>> {
+        match self.resources.get(&TypeId::of::<R>()) {
+            Some(resource) => {
+                Some(RefMut::map(resource.borrow_mut(), |r| r.downcast_mut::<R>().unwrap()))
+            },
+            None => None
+        }
+    }
+}
@@ -1,16 +1,36 @@
 extern crate cgmath;
 
 use entity::Entity;
-use world::{System, SystemData, World};
-use collider::Collider;
+use world::{Stage, System, SystemData, SystemHandle, World, WorldSnapshot};
+use collider::{Collider, CollisionManifold};
+use commandbuffer::CommandBuffer;
 use component::{Component, ComponentId};
 use rectangle::Rectangle;
 use renderer::Renderer;
 use std::vec::Vec;
 use std::collections::hash_map::HashMap;
 use std::rc::Rc;
-use self::cgmath::Vector2;
+use std::any::Any;
+use std::cell::{Ref, RefMut};
+use self::cgmath::{InnerSpace, Vector2};
 use engine::Engine;
+use query::{Fetch, FetchMut, Query, QueryMut};
+use events::EventReader;
+use taglists::TagFilter;
+use serialization::SerializableComponent;
+use prefab::{Prefab, PrefabComponent};
+use serde_json::Value;
+use utils;
+use log::Log;
+use std::path::Path;
+use std::cmp::Ordering;
+use depthcomponent::DepthComponent;
+use transformcomponent::GlobalTransformComponent;
+use spatialhash::SpatialHash;
+use collidercomponent::ColliderComponent;
+
+/// Step between the actual depths of the entities sharing a depth.
+const ACTUAL_DEPTH_THETA: f64 = 0.000001;
 
 pub trait SceneTrait {
     fn before_update();
@@ -26,39 +46,54 @@ pub trait SceneTrait {
     fn add(entity: Entity);
     fn remove(entity: &Entity);
     fn handle_window_resize(old_window_size: Vector2<f32>, new_window_size: Vector2<f32>);
-    fn update_spatial_hash(collider: &Collider);
-    fn collide_check_rect(rect: Rectangle, layer_mask: i32);
-    fn collide_check_split(x: f32, y: f32, w: f32, h: f32, layer_mask: i32);
-    fn collide_check_entity(entity: &Entity, layer_mask: i32);
+    fn update_spatial_hash(entity: Entity, bounds: Rectangle, layer: i32);
+    fn collide_check_rect(rect: Rectangle, layer_mask: i32) -> bool;
+    fn collide_check_split(x: f32, y: f32, w: f32, h: f32, layer_mask: i32) -> bool;
+    fn collide_check_entity(entity: Entity, layer_mask: i32) -> bool;
     //void CollideAll(Entity *e, std::vector<Colliders::Collider *> *collidingColliders, int layerMask = -1);
     //void CollideWith(Colliders::Collider *c, std::vector<Colliders::Collider *> *collidingColliders, int layerMask);
-    //void RayCast(Ray2 *ray, std::vector<Colliders::RayCollisionData *> *rayCollisionData, int layerMask);
+}
+
+/// An entity hit by a ray, see `Scene::raycast`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RaycastHit {
+    pub entity: Entity,
+    pub point: Vector2<f32>,
+    pub normal: Vector2<f32>,
+    pub distance: f32,
 }
 
 pub struct Scene<T> {
     time_active: f32,
     focused: bool,
+    render_when_paused: bool,
     world: World<T>,
-    //tag_lists: TagLists,
     //helper_entity: Entity,
-    //spatial_hash: SpatialHash,
-    actual_depth_lookup: HashMap<i32, f32>,
+    spatial_hash: SpatialHash,
+    // f64 so that the depth of thousands of entities stays unique even for large depths
+    actual_depth_lookup: HashMap<i32, f64>,
+    y_sort: bool,
     tmp_rect: Rectangle,
     colliding_bodies: Vec<Rc<Collider>>,
     renderers: Vec<Rc<Renderer<T>>>,
 }
 
-impl<T> Scene<T> {
+impl<T: 'static> Scene<T> {
     pub fn new(cell_size: u32) -> Self {
-        let s = Scene {
+        let mut s = Scene {
             time_active: 0.0,
             focused: false,
+            render_when_paused: false,
             world: World::new(),
+            spatial_hash: SpatialHash::new(cell_size),
             actual_depth_lookup: HashMap::new(),
+            y_sort: false,
             tmp_rect: Rectangle::new(0.0, 0.0, 0, 0),
             colliding_bodies: Vec::new(),
             renderers: Vec::new(),
         };
+        s.world.register_component_with_storage::<DepthComponent>();
+        s.world.register_component_with_storage::<ColliderComponent>();
         s
     }
 
@@ -66,6 +101,14 @@ impl<T> Scene<T> {
         self.world.create_entity()
     }
 
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.world.is_alive(entity)
+    }
+
+    pub fn get_entity(&self, index: usize) -> Option<Entity> {
+        self.world.get_entity(index)
+    }
+
     pub fn render_entities(&self) {
         //self.world.render_entities();
     }
@@ -76,31 +119,59 @@ impl<T> Scene<T> {
 
     pub fn begin(&mut self) {
         self.focused = true;
-        //for entity in self.world.get_entities() {
-            //entity.scene_begin();
-        //}
+        self.world.scene_begin();
     }
 
     pub fn end(&mut self) {
         self.focused = false;
-        //for entity in self.world.get_entities() {
-            //entity.scene_end();
-        //}
+        self.world.scene_end();
+    }
+
+    /// Called by the SceneManager when another scene is pushed on top of this one.
+    pub fn pause(&mut self) {
+        self.focused = false;
+    }
+
+    /// Called by the SceneManager when this scene is back on top of the stack.
+    pub fn resume(&mut self) {
+        self.focused = true;
     }
 
-    pub fn before_update(&mut self) {
-        //timeActive += Game::deltaTime;
+    pub fn is_focused(&self) -> bool {
+        return self.focused;
+    }
 
-        //self.world.update_lists();
-        //tagLists.UpdateLists();
+    /// Keeps rendering the scene while another one is on top of it.
+    pub fn set_render_when_paused(&mut self, value: bool) {
+        self.render_when_paused = value;
+    }
+
+    pub fn get_render_when_paused(&self) -> bool {
+        return self.render_when_paused;
     }
 
-    pub fn update(&self) {
-        //self.world.update();
+    pub fn before_update(&mut self, dt: f32, user_data: &mut T) {
+        self.time_active += dt;
+
+        //self.world.update_lists();
+        self.world.process_stage(Stage::PreUpdate, dt, self, user_data);
     }
 
-    pub fn after_update(&self) {
+    pub fn update(&self, dt: f32, user_data: &mut T) {
+        self.world.process_stage(Stage::Update, dt, self, user_data);
+    }
 
+    /// Sync point of the frame: the commands recorded by the systems are applied
+    /// once the PostUpdate stage is done and the world transforms are brought up to date,
+    /// then stale events and change trackers are cleared.
+    pub fn after_update(&mut self, dt: f32, user_data: &mut T) {
+        self.world.process_stage(Stage::PostUpdate, dt, self, user_data);
+        self.world.apply_commands();
+        self.world.propagate_transforms();
+        self.update_colliders();
+        self.remove_dead_from_spatial_hash();
+        self.world.update_events();
+        self.world.clear_trackers();
     }
 
     pub fn before_render(&self) {
@@ -125,23 +196,764 @@ impl<T> Scene<T> {
         self.world.register_component_with_storage::<C>();
     }
 
+    pub fn register_serializable_component<C: SerializableComponent>(&mut self) {
+        self.world.register_serializable_component::<C>();
+    }
+
+    pub fn save(&self) -> String {
+        return self.world.save();
+    }
+
+    pub fn save_entities(&self, entities: &[Entity]) -> String {
+        return self.world.save_entities(entities);
+    }
+
+    pub fn load(&mut self, json: &str) -> Option<Vec<Entity>> {
+        return self.world.load(json);
+    }
+
+    pub fn register_snapshot_component<C: Component>(&mut self) where C::Storage: Clone {
+        self.world.register_snapshot_component::<C>();
+    }
+
+    pub fn snapshot(&self) -> WorldSnapshot {
+        return self.world.snapshot();
+    }
+
+    /// Brings the world back to the snapshot and rebuilds the spatial hash from the restored
+    /// ColliderComponents. Entities added with `update_spatial_hash` have to be added again.
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) {
+        self.world.restore(snapshot);
+        self.spatial_hash.clear();
+        self.update_colliders();
+        self.rebuild_actual_depth_lookup();
+    }
+
+    pub fn register_prefab_component<C: PrefabComponent>(&mut self) {
+        self.world.register_prefab_component::<C>();
+    }
+
+    pub fn add_prefab(&mut self, name: &str, prefab: Prefab) {
+        self.world.add_prefab(name, prefab);
+    }
+
+    pub fn load_prefab(&mut self, name: &str, json: &str) -> bool {
+        return self.world.load_prefab(name, json);
+    }
+
+    /// Loads the prefab from a JSON file. Returns false if it can't be read or isn't valid.
+    pub fn load_prefab_file(&mut self, name: &str, path: &Path) -> bool {
+        match utils::load_string_from_file(path) {
+            Some(json) => {
+                return self.world.load_prefab(name, &json);
+            },
+            None => {
+                Log::error(&format!("Cannot read prefab {}", name));
+                return false;
+            }
+        }
+    }
+
+    pub fn has_prefab(&self, name: &str) -> bool {
+        return self.world.has_prefab(name);
+    }
+
+    pub fn spawn_prefab(&mut self, name: &str) -> Option<Entity> {
+        return self.world.spawn_prefab(name);
+    }
+
+    pub fn spawn_prefab_with(&mut self, name: &str, components: Value) -> Option<Entity> {
+        return self.world.spawn_prefab_with(name, components);
+    }
+
+    pub fn spawn_prefab_at(&mut self, name: &str, x: f32, y: f32) -> Option<Entity> {
+        return self.world.spawn_prefab_at(name, x, y);
+    }
+
+    pub fn spawn_prefab_from(&mut self, prefab: &Prefab) -> Option<Entity> {
+        return self.world.spawn_prefab_from(prefab);
+    }
+
     pub fn add_component_to_entity<C: Component>(&mut self, entity: Entity, component: C) {
         self.world.add_component_to_entity(entity, component);
     }
 
+    pub fn remove_component_from_entity<C: Component>(&mut self, entity: Entity) {
+        self.world.remove_component_from_entity::<C>(entity);
+    }
+
     pub fn get_component<C: Component>(&self, entity: Entity) -> Option<&C> {
         return self.world.get_component_for_entity::<C>(entity);
     }
 
+    pub fn get_component_mut<C: Component>(&mut self, entity: Entity) -> Option<&mut C> {
+        return self.world.get_component_mut::<C>(entity);
+    }
+
+    pub fn with_component_mut<C: Component, R, F: FnOnce(&mut C) -> R>(&mut self, entity: Entity, f: F) -> Option<R> {
+        return self.world.with_component_mut::<C, R, F>(entity, f);
+    }
+
+    pub fn get_components_of_type<C: Component>(&self) -> &C::Storage {
+        return self.world.get_components_of_type::<C>();
+    }
+
+    pub fn get_components_of_type_mut<C: Component>(&mut self) -> &mut C::Storage {
+        return self.world.get_components_of_type_mut::<C>();
+    }
+
+    pub fn is_added<C: Component>(&self, entity: Entity) -> bool {
+        return self.world.is_added::<C>(entity);
+    }
+
+    pub fn is_changed<C: Component>(&self, entity: Entity) -> bool {
+        return self.world.is_changed::<C>(entity);
+    }
+
+    pub fn removed_components<C: Component>(&self) -> Vec<Entity> {
+        return self.world.removed_components::<C>();
+    }
+
+    pub fn query<'a, Q: Fetch<'a>>(&'a self) -> Query<'a, Q> {
+        return self.world.query::<Q>();
+    }
+
+    pub fn query_mut<Q>(&mut self) -> QueryMut<Q> where Q: for<'a> FetchMut<'a> {
+        return self.world.query_mut::<Q>();
+    }
+
+    pub fn insert_resource<R: Any>(&mut self, resource: R) {
+        self.world.insert_resource(resource);
+    }
+
+    pub fn remove_resource<R: Any>(&mut self) -> Option<R> {
+        return self.world.remove_resource::<R>();
+    }
+
+    pub fn resource<R: Any>(&self) -> Option<Ref<R>> {
+        return self.world.resource::<R>();
+    }
+
+    pub fn resource_mut<R: Any>(&self) -> Option<RefMut<R>> {
+        return self.world.resource_mut::<R>();
+    }
+
+    pub fn add_event<E: Any>(&mut self) {
+        self.world.add_event::<E>();
+    }
+
+    pub fn send_event<E: Any>(&self, event: E) {
+        self.world.send_event(event);
+    }
+
+    pub fn event_reader<E: Any>(&self) -> EventReader<E> {
+        return self.world.event_reader::<E>();
+    }
+
+    pub fn read_events<E: Any + Clone>(&self, reader: &EventReader<E>) -> Vec<E> {
+        return self.world.read_events(reader);
+    }
+
+    pub fn commands(&self) -> &CommandBuffer<T> {
+        return self.world.commands();
+    }
+
     pub fn destroy_entity(&mut self, entity: Entity) {
         self.world.destroy_entity(entity);
+        self.spatial_hash.remove(entity);
+    }
+
+    pub fn add_tag(&mut self, entity: Entity, tag: u32) {
+        self.world.add_tag(entity, tag);
+    }
+
+    pub fn remove_tag(&mut self, entity: Entity, tag: u32) {
+        self.world.remove_tag(entity, tag);
+    }
+
+    pub fn has_tag(&self, entity: Entity, tag: u32) -> bool {
+        return self.world.has_tag(entity, tag);
+    }
+
+    pub fn get_entities_with_tag(&self, tag: u32) -> &[Entity] {
+        return self.world.get_entities_with_tag(tag);
+    }
+
+    pub fn first_with_tag(&self, tag: u32) -> Option<Entity> {
+        return self.world.first_with_tag(tag);
+    }
+
+    pub fn count_with_tag(&self, tag: u32) -> usize {
+        return self.world.count_with_tag(tag);
+    }
+
+    pub fn get_entities_matching(&self, filter: &TagFilter) -> Vec<Entity> {
+        return self.world.get_entities_matching(filter);
+    }
+
+    pub fn set_name(&mut self, entity: Entity, name: &str) -> bool {
+        return self.world.set_name(entity, name);
+    }
+
+    pub fn set_unique_name(&mut self, entity: Entity, name: &str) -> bool {
+        return self.world.set_unique_name(entity, name);
     }
 
-    pub fn add_system<S: System<T>>(&mut self, system: S) {
-        self.world.add_system(system);
+    pub fn remove_name(&mut self, entity: Entity) {
+        self.world.remove_name(entity);
+    }
+
+    pub fn get_name(&self, entity: Entity) -> Option<&str> {
+        return self.world.get_name(entity);
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        return self.world.find_by_name(name);
+    }
+
+    pub fn find_all_by_name(&self, name: &str) -> &[Entity] {
+        return self.world.find_all_by_name(name);
+    }
+
+    pub fn get_debug_label(&self, entity: Entity) -> String {
+        return self.world.get_debug_label(entity);
+    }
+
+    /// Adds the entity to the spatial hash with the given bounds and collision layer,
+    /// or moves it there if it's already in. Call it again whenever it moves.
+    pub fn update_spatial_hash(&mut self, entity: Entity, bounds: Rectangle, layer: i32) {
+        if !self.world.is_alive(entity) {
+            return;
+        }
+        self.spatial_hash.update(entity, bounds, layer);
+    }
+
+    pub fn remove_from_spatial_hash(&mut self, entity: Entity) {
+        self.spatial_hash.remove(entity);
+    }
+
+    /// Drops the entities that have been destroyed, e.g. by a command, from the spatial hash.
+    fn remove_dead_from_spatial_hash(&mut self) {
+        for entity in self.spatial_hash.get_entities() {
+            if !self.world.is_alive(entity) {
+                self.spatial_hash.remove(entity);
+            }
+        }
+    }
+
+    /// Moves the ColliderComponents with their entities and brings the spatial hash
+    /// up to date with the ones that moved, changed, were added or removed.
+    fn update_colliders(&mut self) {
+        for entity in self.world.removed_components::<ColliderComponent>() {
+            self.spatial_hash.remove(entity);
+        }
+        let mut moved = Vec::new();
+        for (entity, (collider, global)) in self.world.query::<(&ColliderComponent, Option<&GlobalTransformComponent>)>().iter() {
+            let (position, rotation) = match global {
+                Some(global) => (*global.get_position(), global.get_rotation()),
+                None => (Vector2::new(0.0, 0.0), 0.0)
+            };
+            if position != collider.get_entity_position() || rotation != collider.get_entity_rotation() ||
+               !self.spatial_hash.contains(entity) || self.world.is_changed::<ColliderComponent>(entity) {
+                moved.push((entity, position, rotation));
+            }
+        }
+        for (entity, position, rotation) in moved {
+            if let Some(collider) = self.world.get_component_mut::<ColliderComponent>(entity) {
+                if collider.get_entity_position() != position {
+                    collider.set_entity_position(position);
+                }
+                if collider.get_entity_rotation() != rotation {
+                    collider.set_entity_rotation(rotation);
+                }
+                match collider.get_bounds() {
+                    Some(bounds) => self.spatial_hash.update(entity, bounds, collider.get_layer()),
+                    None => self.spatial_hash.remove(entity)
+                }
+            }
+        }
+    }
+
+    pub fn get_world(&self) -> &World<T> {
+        return &self.world;
+    }
+
+    pub fn get_spatial_hash(&self) -> &SpatialHash {
+        return &self.spatial_hash;
+    }
+
+    /// The entities in the spatial hash overlapping the rectangle, whose layer shares a bit
+    /// with `layer_mask`. Pass -1 to check every layer.
+    pub fn collide_all_rect(&self, rect: Rectangle, layer_mask: i32) -> Vec<Entity> {
+        return self.collide_all_split(rect.x, rect.y, rect.w as f32, rect.h as f32, layer_mask);
+    }
+
+    pub fn collide_all_split(&self, x: f32, y: f32, w: f32, h: f32, layer_mask: i32) -> Vec<Entity> {
+        let mut entities = self.spatial_hash.query_split(x, y, w, h, layer_mask);
+        entities.retain(|entity| self.world.is_alive(*entity));
+        return entities;
+    }
+
+    pub fn collide_check_rect(&self, rect: Rectangle, layer_mask: i32) -> bool {
+        return !self.collide_all_rect(rect, layer_mask).is_empty();
+    }
+
+    pub fn collide_check_split(&self, x: f32, y: f32, w: f32, h: f32, layer_mask: i32) -> bool {
+        return !self.collide_all_split(x, y, w, h, layer_mask).is_empty();
+    }
+
+    /// Whether the entity collides with another entity in the spatial hash, see `collide_all`.
+    pub fn collide_check_entity(&self, entity: Entity, layer_mask: i32) -> bool {
+        return !self.collide_all(entity, layer_mask).is_empty();
+    }
+
+    /// Whether the ColliderComponents of the entities overlap and each one is on a layer
+    /// the other one collides with.
+    pub fn collides(&self, entity: Entity, other: Entity) -> bool {
+        if entity == other {
+            return false;
+        }
+        match (self.world.get_component_for_entity::<ColliderComponent>(entity),
+               self.world.get_component_for_entity::<ColliderComponent>(other)) {
+            (Some(collider), Some(other_collider)) => {
+                return collider.can_collide_with(other_collider) && collider.collide(other_collider);
+            },
+            _ => return false
+        }
+    }
+
+    /// How the ColliderComponents of the entities overlap, one manifold for each pair of
+    /// overlapping shapes, the normals going from `entity` towards `other`. Empty if they don't collide.
+    pub fn get_manifolds(&self, entity: Entity, other: Entity) -> Vec<CollisionManifold> {
+        if !self.collides(entity, other) {
+            return Vec::new();
+        }
+        let collider = self.world.get_component_for_entity::<ColliderComponent>(entity).unwrap();
+        let other_collider = self.world.get_component_for_entity::<ColliderComponent>(other).unwrap();
+        return collider.collide_manifolds(other_collider);
+    }
+
+    /// The entities colliding with the given one whose layer shares a bit with `layer_mask`,
+    /// e.g. the mask of its ColliderComponent or -1 for every layer. When both have a
+    /// ColliderComponent their layers and masks have to match too, like in `collides`.
+    /// Entities added to the spatial hash without a ColliderComponent are tested with their bounds.
+    pub fn collide_all(&self, entity: Entity, layer_mask: i32) -> Vec<Entity> {
+        let bounds = match self.spatial_hash.get_bounds(entity) {
+            Some(bounds) => bounds,
+            None => return Vec::new()
+        };
+        let collider = self.world.get_component_for_entity::<ColliderComponent>(entity);
+        let mut entities = self.collide_all_rect(bounds, layer_mask);
+        entities.retain(|other| {
+            if *other == entity {
+                return false;
+            }
+            match (collider, self.world.get_component_for_entity::<ColliderComponent>(*other)) {
+                (Some(collider), Some(other_collider)) => {
+                    collider.can_collide_with(other_collider) && collider.collide(other_collider)
+                },
+                _ => true
+            }
+        });
+        return entities;
+    }
+
+    /// The closest entity hit by the ray going from `origin` along `direction` within
+    /// `max_distance`, among the ones whose layer shares a bit with `layer_mask`.
+    /// Entities whose shapes contain the origin are hit at distance 0.
+    pub fn raycast(&self, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32, layer_mask: i32) -> Option<RaycastHit> {
+        if direction.x == 0.0 && direction.y == 0.0 {
+            return None;
+        }
+        let mut closest: Option<RaycastHit> = None;
+        let mut max_distance = max_distance;
+        for entity in self.spatial_hash.query_ray(origin, direction.normalize(), max_distance, layer_mask) {
+            match self.raycast_entity(entity, origin, direction, max_distance) {
+                Some(hit) => {
+                    if closest.map_or(true, |closest| hit.distance < closest.distance) {
+                        // farther entities can't be the closest one anymore
+                        max_distance = hit.distance;
+                        closest = Some(hit);
+                    }
+                },
+                None => {}
+            }
+        }
+        return closest;
+    }
+
+    /// Every entity hit by the ray, the closest first. Only the entities in the cells of the
+    /// spatial hash crossed by the ray are tested. The ones without ColliderComponent are
+    /// tested with their bounds.
+    pub fn raycast_all(&self, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32, layer_mask: i32) -> Vec<RaycastHit> {
+        let mut hits = Vec::new();
+        if direction.x == 0.0 && direction.y == 0.0 {
+            return hits;
+        }
+        for entity in self.spatial_hash.query_ray(origin, direction.normalize(), max_distance, layer_mask) {
+            match self.raycast_entity(entity, origin, direction, max_distance) {
+                Some(hit) => hits.push(hit),
+                None => {}
+            }
+        }
+        hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal));
+        return hits;
+    }
+
+    fn raycast_entity(&self, entity: Entity, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RaycastHit> {
+        if !self.world.is_alive(entity) {
+            return None;
+        }
+        let hit = match self.world.get_component_for_entity::<ColliderComponent>(entity) {
+            Some(collider) => collider.raycast(origin, direction, max_distance),
+            None => {
+                match self.spatial_hash.get_bounds(entity) {
+                    Some(bounds) => Collider::raycast_rect(bounds, origin, direction, max_distance),
+                    None => None
+                }
+            }
+        };
+        hit.map(|hit| RaycastHit {
+            entity: entity,
+            point: hit.point,
+            normal: hit.normal,
+            distance: hit.distance,
+        })
+    }
+
+    /// Sets the depth of the entity, see DepthComponent. Among the entities with the same depth,
+    /// the ones that got it later are drawn on top.
+    pub fn set_depth(&mut self, entity: Entity, depth: i32) {
+        if !self.world.is_alive(entity) {
+            return;
+        }
+        let actual_depth = self.next_actual_depth(depth);
+        match self.world.get_component_mut::<DepthComponent>(entity) {
+            Some(dc) => {
+                *dc = DepthComponent::new(depth, actual_depth);
+                return;
+            },
+            None => {}
+        }
+        self.world.add_component_to_entity(entity, DepthComponent::new(depth, actual_depth));
+    }
+
+    /// Gives the entity a new actual depth, bringing it on top of the entities with the same depth.
+    pub fn set_actual_depth(&mut self, entity: Entity) {
+        let depth = self.get_depth(entity);
+        self.set_depth(entity, depth);
+    }
+
+    /// Starts the actual depths handed out next above the ones of the restored entities.
+    fn rebuild_actual_depth_lookup(&mut self) {
+        self.actual_depth_lookup.clear();
+        for (_, dc) in self.world.query::<&DepthComponent>().iter() {
+            let next = dc.get_depth() as f64 - dc.get_actual_depth() + ACTUAL_DEPTH_THETA;
+            let add = self.actual_depth_lookup.entry(dc.get_depth()).or_insert(next);
+            if *add < next {
+                *add = next;
+            }
+        }
+    }
+
+    fn next_actual_depth(&mut self, depth: i32) -> f64 {
+        let add = match self.actual_depth_lookup.get_mut(&depth) {
+            Some(add) => {
+                let current = *add;
+                *add += ACTUAL_DEPTH_THETA;
+                current
+            },
+            None => {
+                self.actual_depth_lookup.insert(depth, ACTUAL_DEPTH_THETA);
+                0.0
+            }
+        };
+        depth as f64 - add
+    }
+
+    pub fn get_depth(&self, entity: Entity) -> i32 {
+        match self.world.get_component_for_entity::<DepthComponent>(entity) {
+            Some(dc) => {
+                return dc.get_depth();
+            },
+            None => {
+                return 0;
+            }
+        }
+    }
+
+    pub fn get_actual_depth(&self, entity: Entity) -> f64 {
+        match self.world.get_component_for_entity::<DepthComponent>(entity) {
+            Some(dc) => {
+                return dc.get_actual_depth();
+            },
+            None => {
+                return 0.0;
+            }
+        }
+    }
+
+    /// Draws the entities with the same depth from the top of the screen to the bottom,
+    /// for top-down games. Off by default.
+    pub fn set_y_sort(&mut self, value: bool) {
+        self.y_sort = value;
+    }
+
+    pub fn get_y_sort(&self) -> bool {
+        return self.y_sort;
+    }
+
+    /// The entities in the order they have to be drawn, from the back to the front.
+    /// The systems of the Render stage process their entities in this order.
+    pub fn get_entities_in_draw_order(&self, entities: &[Entity]) -> Vec<Entity> {
+        let mut sorted = entities.to_vec();
+        if self.y_sort {
+            sorted.sort_by(|a, b| {
+                self.get_depth(*b).cmp(&self.get_depth(*a))
+                    .then_with(|| self.get_draw_y(*a).partial_cmp(&self.get_draw_y(*b)).unwrap_or(Ordering::Equal))
+                    .then_with(|| self.compare_actual_depth(*a, *b))
+            });
+        } else {
+            sorted.sort_by(|a, b| self.compare_actual_depth(*a, *b));
+        }
+        sorted
+    }
+
+    fn compare_actual_depth(&self, a: Entity, b: Entity) -> Ordering {
+        self.get_actual_depth(b).partial_cmp(&self.get_actual_depth(a)).unwrap_or(Ordering::Equal)
+    }
+
+    fn get_draw_y(&self, entity: Entity) -> f32 {
+        match self.world.get_component_for_entity::<GlobalTransformComponent>(entity) {
+            Some(tc) => tc.get_position().y,
+            None => 0.0
+        }
+    }
+
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        self.world.set_parent(child, parent);
+    }
+
+    pub fn remove_parent(&mut self, child: Entity) {
+        self.world.remove_parent(child);
+    }
+
+    pub fn get_parent(&self, entity: Entity) -> Option<Entity> {
+        return self.world.get_parent(entity);
+    }
+
+    pub fn get_children(&self, entity: Entity) -> Vec<Entity> {
+        return self.world.get_children(entity);
+    }
+
+    pub fn add_system<S: System<T>>(&mut self, system: S) -> SystemHandle {
+        return self.world.add_system(system);
+    }
+
+    pub fn add_system_to_stage<S: System<T>>(&mut self, stage: Stage, system: S) -> SystemHandle {
+        return self.world.add_system_to_stage(stage, system);
+    }
+
+    pub fn remove_system(&mut self, system: SystemHandle) -> bool {
+        return self.world.remove_system(system);
+    }
+
+    pub fn run_before(&mut self, system: SystemHandle, other: SystemHandle) {
+        self.world.run_before(system, other);
+    }
+
+    pub fn run_after(&mut self, system: SystemHandle, other: SystemHandle) {
+        self.world.run_after(system, other);
+    }
+
+    pub fn set_system_enabled(&mut self, system: SystemHandle, enabled: bool) {
+        self.world.set_system_enabled(system, enabled);
+    }
+
+    pub fn is_system_enabled(&self, system: SystemHandle) -> bool {
+        return self.world.is_system_enabled(system);
+    }
+
+    pub fn process_stage(&self, stage: Stage, dt: f32, user_data: &mut T) {
+        self.world.process_stage(stage, dt, self, user_data);
     }
 
     pub fn process(&self, dt: f32, user_data: &mut T) {
         self.world.process(dt, &self, user_data);
     } 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate cgmath;
+
+    use collider::{BoxCollider, CircleCollider, ColliderShape, PolygonCollider};
+    use collidercomponent::ColliderComponent;
+    use depthcomponent::DepthComponent;
+    use entity::Entity;
+    use rectangle::Rectangle;
+    use scene::Scene;
+    use transformcomponent::{GlobalTransformComponent, TransformComponent};
+    use self::cgmath::Vector2;
+
+    fn collider_scene() -> Scene<()> {
+        let mut scene: Scene<()> = Scene::new(32);
+        scene.register_component::<TransformComponent>();
+        scene.register_component::<GlobalTransformComponent>();
+        scene
+    }
+
+    fn snapshot_scene() -> Scene<()> {
+        let mut scene = collider_scene();
+        scene.register_snapshot_component::<TransformComponent>();
+        scene.register_snapshot_component::<GlobalTransformComponent>();
+        scene.register_snapshot_component::<ColliderComponent>();
+        scene.register_snapshot_component::<DepthComponent>();
+        scene
+    }
+
+    fn spawn_collider(scene: &mut Scene<()>, x: f32, y: f32, collider: ColliderComponent) -> Entity {
+        let entity = scene.create_entity();
+        let mut tc = TransformComponent::new();
+        tc.set_position(x, y);
+        scene.add_component_to_entity(entity, tc);
+        scene.add_component_to_entity(entity, GlobalTransformComponent::new());
+        scene.add_component_to_entity(entity, collider);
+        entity
+    }
+
+    fn square(size: f32) -> ColliderComponent {
+        ColliderComponent::new(ColliderShape::Box(BoxCollider::new(Vector2::new(-size / 2.0, -size / 2.0), size, size)))
+    }
+
+    fn circle(radius: f32) -> ColliderComponent {
+        ColliderComponent::new(ColliderShape::Circle(CircleCollider::new(radius, Vector2::new(0.0, 0.0))))
+    }
+
+    #[test]
+    fn entities_are_drawn_by_depth_then_by_actual_depth() {
+        let mut scene: Scene<()> = Scene::new(32);
+        let a = scene.create_entity();
+        let b = scene.create_entity();
+        let c = scene.create_entity();
+        let d = scene.create_entity();
+        scene.set_depth(a, 0);
+        scene.set_depth(b, 100);
+        scene.set_depth(c, 0);
+        scene.set_depth(d, 100);
+        assert!(scene.get_actual_depth(b) != scene.get_actual_depth(d));
+        // deeper entities first, and among the same depth the latest one on top
+        assert_eq!(scene.get_entities_in_draw_order(&[a, b, c, d]), vec![b, d, a, c]);
+        scene.set_actual_depth(a);
+        assert_eq!(scene.get_entities_in_draw_order(&[a, b, c, d]), vec![b, d, c, a]);
+    }
+
+    #[test]
+    fn y_sort_draws_the_same_depth_from_top_to_bottom() {
+        let mut scene: Scene<()> = Scene::new(32);
+        scene.register_component::<TransformComponent>();
+        let a = scene.create_entity();
+        let b = scene.create_entity();
+        let c = scene.create_entity();
+        scene.set_depth(a, 0);
+        scene.set_depth(b, 100);
+        scene.set_depth(c, 0);
+        scene.set_actual_depth(a);
+        let mut tc = TransformComponent::new();
+        tc.set_position(0.0, 50.0);
+        scene.add_component_to_entity(c, tc);
+        let mut tc = TransformComponent::new();
+        tc.set_position(0.0, 10.0);
+        scene.add_component_to_entity(a, tc);
+        scene.after_update(0.0, &mut ());
+        scene.set_y_sort(true);
+        assert_eq!(scene.get_entities_in_draw_order(&[a, b, c]), vec![b, a, c]);
+        scene.set_y_sort(false);
+        assert_eq!(scene.get_entities_in_draw_order(&[a, b, c]), vec![b, c, a]);
+    }
+
+    #[test]
+    fn restoring_rebuilds_the_spatial_hash_and_the_actual_depths() {
+        let mut scene = snapshot_scene();
+        let a = spawn_collider(&mut scene, 0.0, 0.0, square(10.0));
+        let b = spawn_collider(&mut scene, 100.0, 0.0, circle(4.0));
+        scene.set_depth(a, 0);
+        scene.set_depth(b, 0);
+        scene.after_update(0.0, &mut ());
+        let snapshot = scene.snapshot();
+
+        scene.with_component_mut::<TransformComponent, _, _>(b, |tc| tc.set_position(8.0, 0.0));
+        let c = spawn_collider(&mut scene, 50.0, 0.0, square(2.0));
+        scene.after_update(0.0, &mut ());
+        assert_eq!(scene.collide_all(a, -1), vec![b]);
+
+        scene.restore(&snapshot);
+        assert!(!scene.get_world().is_alive(c));
+        assert_eq!(scene.get_spatial_hash().len(), 2);
+        assert!(scene.collide_all(a, -1).is_empty());
+        let hit = scene.raycast(Vector2::new(20.0, 0.0), Vector2::new(1.0, 0.0), 1000.0, -1).unwrap();
+        assert_eq!((hit.entity, hit.distance), (b, 76.0));
+
+        // a scene that never handed out actual depths continues after the restored ones
+        let mut other = snapshot_scene();
+        other.restore(&snapshot);
+        assert_eq!(other.collide_all_rect(Rectangle::new(90.0, -10.0, 20, 20), -1), vec![b]);
+        let d = other.create_entity();
+        other.set_depth(d, 0);
+        assert_eq!(other.get_entities_in_draw_order(&[d, a, b]), vec![a, b, d]);
+    }
+
+    #[test]
+    fn collide_all_follows_the_layers_and_masks_of_both_colliders() {
+        let mut scene = collider_scene();
+        let a = spawn_collider(&mut scene, 0.0, 0.0, square(10.0));
+        let b = spawn_collider(&mut scene, 6.0, 0.0, circle(4.0).with_layer(2).with_mask(1));
+        // a collides with layer 4 but c doesn't collide with layer 1
+        let c = spawn_collider(&mut scene, -6.0, 0.0, circle(4.0).with_layer(4).with_mask(2));
+        // compound: the second shape reaches a
+        let d = spawn_collider(&mut scene, 300.0, 0.0, square(4.0)
+            .with_shape(ColliderShape::Circle(CircleCollider::new(3.0, Vector2::new(-293.0, 0.0)))));
+        scene.after_update(0.0, &mut ());
+        assert!(scene.collides(a, b) && !scene.collides(a, c) && scene.collides(a, d));
+        let mut all = scene.collide_all(a, -1);
+        all.sort_by_key(|e| e.get_index());
+        assert_eq!(all, vec![b, d]);
+        assert_eq!(scene.collide_all(a, 2), vec![b]);
+        assert!(scene.collide_all(c, -1).is_empty());
+        let mut all = scene.collide_all(d, -1);
+        all.sort_by_key(|e| e.get_index());
+        assert_eq!(all, vec![a, b]);
+    }
+
+    #[test]
+    fn rays_hit_the_closest_entities_first() {
+        let mut scene = collider_scene();
+        let a = spawn_collider(&mut scene, 50.0, 0.0, square(10.0));
+        let b = spawn_collider(&mut scene, 100.0, 0.0, circle(5.0).with_layer(2));
+        let diamond = PolygonCollider::new(Vector2::new(0.0, 0.0),
+            vec![Vector2::new(-5.0, 0.0), Vector2::new(0.0, -5.0), Vector2::new(5.0, 0.0), Vector2::new(0.0, 5.0)]).unwrap();
+        let c = spawn_collider(&mut scene, 150.0, 0.0, ColliderComponent::new(ColliderShape::Polygon(diamond)));
+        // in the spatial hash without ColliderComponent, hit through its bounds
+        let d = scene.create_entity();
+        scene.update_spatial_hash(d, Rectangle::new(195.0, -5.0, 10, 10), 1);
+        scene.after_update(0.0, &mut ());
+
+        let right = Vector2::new(1.0, 0.0);
+        let hit = scene.raycast(Vector2::new(0.0, 0.0), right, 1000.0, -1).unwrap();
+        assert_eq!((hit.entity, hit.point, hit.normal, hit.distance), (a, Vector2::new(45.0, 0.0), Vector2::new(-1.0, 0.0), 45.0));
+        let hits: Vec<(Entity, f32)> = scene.raycast_all(Vector2::new(0.0, 0.0), right, ::std::f32::INFINITY, -1)
+            .iter().map(|hit| (hit.entity, hit.distance)).collect();
+        assert_eq!(hits, vec![(a, 45.0), (b, 95.0), (c, 145.0), (d, 195.0)]);
+        let hits: Vec<Entity> = scene.raycast_all(Vector2::new(300.0, 0.0), -right, 160.0, -1).iter().map(|hit| hit.entity).collect();
+        assert_eq!(hits, vec![d, c]);
+
+        assert_eq!(scene.raycast(Vector2::new(0.0, 0.0), right, 1000.0, 2).unwrap().entity, b);
+        assert!(scene.raycast(Vector2::new(0.0, 0.0), right, 40.0, -1).is_none());
+        let hit = scene.raycast(Vector2::new(52.0, 1.0), right, 1000.0, -1).unwrap();
+        assert_eq!((hit.entity, hit.distance), (a, 0.0));
+        let hit = scene.raycast(Vector2::new(100.0, -50.0), Vector2::new(0.0, 2.0), ::std::f32::INFINITY, -1).unwrap();
+        assert_eq!((hit.entity, hit.normal, hit.distance), (b, Vector2::new(0.0, -1.0), 45.0));
+        assert!(scene.raycast(Vector2::new(0.0, 100.0), right, ::std::f32::INFINITY, -1).is_none());
+        assert!(scene.raycast(Vector2::new(0.0, 0.0), Vector2::new(0.0, 0.0), 1000.0, -1).is_none());
+    }
+}
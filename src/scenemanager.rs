@@ -0,0 +1,238 @@
+use color::Color;
+use scene::Scene;
+use log::Log;
+use std::option::Option;
+use std::vec::Vec;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransitionKind {
+    /// The whole screen fades to the color and back.
+    Fade,
+    /// The color sweeps over the screen from the left and back.
+    Wipe,
+}
+
+/// Covers the screen while the scene stack changes. The change happens halfway through,
+/// when the screen is fully covered. Drawn by the quad shader when the screen render target
+/// is copied to the window.
+#[derive(Debug, Copy, Clone)]
+pub struct Transition {
+    kind: TransitionKind,
+    duration: f32,
+    color: Color,
+    time: f32,
+}
+
+impl Transition {
+    pub fn new(kind: TransitionKind, duration: f32) -> Self {
+        Transition {
+            kind: kind,
+            duration: duration,
+            color: Color::black(),
+            time: 0.0,
+        }
+    }
+
+    pub fn fade(duration: f32) -> Self {
+        Transition::new(TransitionKind::Fade, duration)
+    }
+
+    pub fn wipe(duration: f32) -> Self {
+        Transition::new(TransitionKind::Wipe, duration)
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn get_kind(&self) -> TransitionKind {
+        self.kind
+    }
+
+    pub fn get_color(&self) -> Color {
+        self.color
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        self.time += dt;
+    }
+
+    /// How much of the screen is covered, from 0 to 1. Rises during the first half
+    /// of the transition and falls during the second one.
+    pub fn get_amount(&self) -> f32 {
+        let half = self.duration / 2.0;
+        if half <= 0.0 {
+            return 0.0;
+        }
+        if self.time < half {
+            return self.time / half;
+        }
+        ((self.duration - self.time) / half).max(0.0)
+    }
+
+    /// Whether the first half is over.
+    pub fn is_covered(&self) -> bool {
+        self.time >= self.duration / 2.0
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.time >= self.duration
+    }
+}
+
+enum SceneChange<T> {
+    Push(Scene<T>),
+    Pop,
+    Replace(Scene<T>),
+}
+
+/// Stack of scenes. Only the top one is updated, the ones below it are paused and
+/// are only rendered if they're set to render when paused, e.g. behind a pause overlay.
+/// `Scene::begin` is called when a scene enters the stack and `Scene::end` when it leaves it.
+pub struct SceneManager<T> {
+    scenes: Vec<Scene<T>>,
+    transition: Option<Transition>,
+    pending: Option<SceneChange<T>>,
+}
+
+impl<T: 'static> SceneManager<T> {
+    pub fn new() -> Self {
+        SceneManager {
+            scenes: Vec::new(),
+            transition: None,
+            pending: None,
+        }
+    }
+
+    /// Pauses the top scene and begins the new one on top of it.
+    pub fn push(&mut self, scene: Scene<T>) {
+        self.apply(SceneChange::Push(scene));
+    }
+
+    /// Ends the top scene and resumes the one below it.
+    pub fn pop(&mut self) -> Option<Scene<T>> {
+        self.apply(SceneChange::Pop)
+    }
+
+    /// Ends the top scene and begins the new one in its place.
+    pub fn replace(&mut self, scene: Scene<T>) -> Option<Scene<T>> {
+        self.apply(SceneChange::Replace(scene))
+    }
+
+    pub fn push_with_transition(&mut self, scene: Scene<T>, transition: Transition) {
+        self.start_transition(SceneChange::Push(scene), transition);
+    }
+
+    /// The popped scene is dropped once it has ended.
+    pub fn pop_with_transition(&mut self, transition: Transition) {
+        self.start_transition(SceneChange::Pop, transition);
+    }
+
+    /// The replaced scene is dropped once it has ended.
+    pub fn replace_with_transition(&mut self, scene: Scene<T>, transition: Transition) {
+        self.start_transition(SceneChange::Replace(scene), transition);
+    }
+
+    fn start_transition(&mut self, change: SceneChange<T>, transition: Transition) {
+        if self.transition.is_some() {
+            Log::warning("A scene transition is already running, ignoring the new one");
+            return;
+        }
+        self.transition = Some(transition);
+        self.pending = Some(change);
+    }
+
+    fn apply(&mut self, change: SceneChange<T>) -> Option<Scene<T>> {
+        match change {
+            SceneChange::Push(scene) => {
+                if let Some(top) = self.scenes.last_mut() {
+                    top.pause();
+                }
+                self.scenes.push(scene);
+                self.scenes.last_mut().unwrap().begin();
+                None
+            },
+            SceneChange::Pop => {
+                let mut popped = self.scenes.pop()?;
+                popped.end();
+                if let Some(top) = self.scenes.last_mut() {
+                    top.resume();
+                }
+                Some(popped)
+            },
+            SceneChange::Replace(scene) => {
+                let replaced = match self.scenes.pop() {
+                    Some(mut replaced) => {
+                        replaced.end();
+                        Some(replaced)
+                    },
+                    None => None
+                };
+                self.scenes.push(scene);
+                self.scenes.last_mut().unwrap().begin();
+                replaced
+            }
+        }
+    }
+
+    /// Advances the running transition, changing the scenes once the screen is covered.
+    pub fn update_transition(&mut self, dt: f32) {
+        let covered = match self.transition {
+            Some(ref mut transition) => {
+                transition.advance(dt);
+                transition.is_covered()
+            },
+            None => false
+        };
+        // also catches transitions that are covered from the start, like the ones lasting 0 seconds
+        if covered {
+            if let Some(change) = self.pending.take() {
+                self.apply(change);
+            }
+        }
+        let finished = match self.transition {
+            Some(ref transition) => transition.is_finished(),
+            None => false
+        };
+        if finished {
+            self.transition = None;
+        }
+    }
+
+    pub fn get_transition(&self) -> Option<&Transition> {
+        self.transition.as_ref()
+    }
+
+    pub fn get_top(&self) -> Option<&Scene<T>> {
+        self.scenes.last()
+    }
+
+    pub fn get_top_mut(&mut self) -> Option<&mut Scene<T>> {
+        self.scenes.last_mut()
+    }
+
+    /// The scenes to render this frame, from the bottom to the top one.
+    pub fn get_visible_scenes(&self) -> &[Scene<T>] {
+        if self.scenes.is_empty() {
+            return &self.scenes;
+        }
+        let mut first = self.scenes.len() - 1;
+        while first > 0 && self.scenes[first - 1].get_render_when_paused() {
+            first -= 1;
+        }
+        &self.scenes[first..]
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+}
@@ -0,0 +1,211 @@
+use component::Component;
+use entity::Entity;
+use hierarchy::ParentComponent;
+use world::World;
+use log::Log;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::vec::Vec;
+
+/// Component that can be saved to JSON. Only the component types registered with
+/// `World::register_serializable_component` are saved, the others are skipped.
+pub trait SerializableComponent: Component + Serialize + DeserializeOwned {
+    /// Key the component is saved under. Has to be unique and stay the same across versions.
+    fn get_name() -> &'static str;
+
+    /// Called on a loaded component before it's attached, to point its entity references
+    /// at the loaded entities. Returning false drops the component, e.g. because it refers
+    /// to an entity that wasn't saved along with it.
+    fn remap_entities(&mut self, _map: &EntityMap) -> bool {
+        true
+    }
+
+    /// Attaches the loaded component to its entity.
+    fn attach<T: 'static>(self, world: &mut World<T>, entity: Entity) {
+        world.add_component_to_entity(entity, self);
+    }
+}
+
+/// Maps the entities found in a save to the ones created while loading it.
+pub struct EntityMap {
+    entities: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    pub fn new() -> Self {
+        EntityMap {
+            entities: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, saved: Entity, loaded: Entity) {
+        self.entities.insert(saved, loaded);
+    }
+
+    /// None if the entity wasn't part of the save.
+    pub fn get(&self, saved: Entity) -> Option<Entity> {
+        self.entities.get(&saved).cloned()
+    }
+}
+
+/// Saves and loads the components of one type, see `World::register_serializable_component`.
+pub struct ComponentSerializer<T> {
+    name: &'static str,
+    save: fn(&World<T>, Entity) -> Option<Value>,
+    load: fn(&mut World<T>, Entity, Value, &EntityMap) -> bool,
+}
+
+impl<T: 'static> ComponentSerializer<T> {
+    pub fn of<C: SerializableComponent>() -> Self {
+        ComponentSerializer {
+            name: C::get_name(),
+            save: save_component::<T, C>,
+            load: load_component::<T, C>,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The entity's component as JSON, None if it doesn't have one.
+    pub fn save(&self, world: &World<T>, entity: Entity) -> Option<Value> {
+        (self.save)(world, entity)
+    }
+
+    /// Returns false if the value isn't a valid component.
+    pub fn load(&self, world: &mut World<T>, entity: Entity, value: Value, map: &EntityMap) -> bool {
+        (self.load)(world, entity, value, map)
+    }
+}
+
+impl<T> Clone for ComponentSerializer<T> {
+    fn clone(&self) -> Self {
+        ComponentSerializer {
+            name: self.name,
+            save: self.save,
+            load: self.load,
+        }
+    }
+}
+
+fn save_component<T: 'static, C: SerializableComponent>(world: &World<T>, entity: Entity) -> Option<Value> {
+    match world.get_component_for_entity::<C>(entity) {
+        Some(component) => {
+            match serde_json::to_value(component) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    Log::error(&format!("Cannot save {}: {}", C::get_name(), e));
+                    None
+                }
+            }
+        },
+        None => None
+    }
+}
+
+fn load_component<T: 'static, C: SerializableComponent>(world: &mut World<T>, entity: Entity, value: Value, map: &EntityMap) -> bool {
+    match serde_json::from_value::<C>(value) {
+        Ok(mut component) => {
+            if component.remap_entities(map) {
+                component.attach(world, entity);
+            }
+            true
+        },
+        Err(e) => {
+            Log::error(&format!("Cannot load {}: {}", C::get_name(), e));
+            false
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub entity: Entity,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub tags: u32,
+    pub components: BTreeMap<String, Value>,
+}
+
+/// What `World::save_entities` writes and `World::load` reads.
+#[derive(Serialize, Deserialize)]
+pub struct SavedScene {
+    pub entities: Vec<SavedEntity>,
+}
+
+impl SerializableComponent for ParentComponent {
+    fn get_name() -> &'static str {
+        "parent"
+    }
+
+    fn remap_entities(&mut self, map: &EntityMap) -> bool {
+        match map.get(self.get_parent()) {
+            Some(parent) => {
+                *self = ParentComponent::new(parent);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// The children lists are rebuilt by attaching the entity to its parent.
+    fn attach<T: 'static>(self, world: &mut World<T>, entity: Entity) {
+        world.set_parent(entity, self.get_parent());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use transformcomponent::TransformComponent;
+    use world::World;
+
+    #[test]
+    fn loaded_entities_are_remapped() {
+        let mut world: World<()> = World::new();
+        world.register_serializable_component::<TransformComponent>();
+        let parent = world.create_entity();
+        let child = world.create_entity();
+        let mut transform = TransformComponent::new();
+        transform.set_position(3.0, 4.0);
+        world.add_component_to_entity(parent, transform);
+        world.add_component_to_entity(child, TransformComponent::new());
+        world.set_parent(child, parent);
+        world.add_tag(child, 2);
+        world.set_name(parent, "parent");
+        let json = world.save_entities(&[parent, child]);
+
+        // the slots of the saved entities are taken in the other world
+        let mut other: World<()> = World::new();
+        other.register_serializable_component::<TransformComponent>();
+        let taken = other.create_entity();
+        other.create_entity();
+        let loaded = other.load(&json).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded[0] != parent && loaded[0] != taken);
+        assert_eq!(other.get_parent(loaded[1]), Some(loaded[0]));
+        assert_eq!(other.get_children(loaded[0]), vec![loaded[1]]);
+        assert!(other.get_children(taken).is_empty());
+        assert!(other.has_tag(loaded[1], 2));
+        assert_eq!(other.get_name(loaded[0]), Some("parent"));
+        assert_eq!(other.get_component_for_entity::<TransformComponent>(loaded[0]).unwrap().get_position().y, 4.0);
+    }
+
+    #[test]
+    fn references_to_unsaved_entities_are_dropped() {
+        let mut world: World<()> = World::new();
+        world.register_serializable_component::<TransformComponent>();
+        let parent = world.create_entity();
+        let child = world.create_entity();
+        world.add_component_to_entity(child, TransformComponent::new());
+        world.set_parent(child, parent);
+        let json = world.save_entities(&[child]);
+        let loaded = world.load(&json).unwrap();
+        assert_eq!(world.get_parent(loaded[0]), None);
+        assert!(world.get_component_for_entity::<TransformComponent>(loaded[0]).is_some());
+        assert!(world.load("{").is_none());
+    }
+}
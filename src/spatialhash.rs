@@ -0,0 +1,430 @@
+extern crate cgmath;
+
+use entity::Entity;
+use log::Log;
+use rectangle::Rectangle;
+use self::cgmath::Vector2;
+use std::collections::{HashMap, HashSet};
+use std::vec::Vec;
+
+/// Where a body is in the hash: its bounds, its collision layer and the cells it covers.
+#[derive(Debug, Copy, Clone)]
+struct SpatialHashBody {
+    bounds: Rectangle,
+    layer: i32,
+    min_cell: (i32, i32),
+    max_cell: (i32, i32),
+}
+
+/// Uniform grid used as a broadphase. Each body is stored in every cell its bounds overlap,
+/// so a query only looks at the bodies sharing a cell with the queried area.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+    bodies: HashMap<Entity, SpatialHashBody>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: u32) -> Self {
+        SpatialHash {
+            cell_size: cell_size.max(1) as f32,
+            cells: HashMap::new(),
+            bodies: HashMap::new(),
+        }
+    }
+
+    pub fn get_cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn get_cell(&self, x: f32, y: f32) -> (i32, i32) {
+        ((x / self.cell_size).floor() as i32, (y / self.cell_size).floor() as i32)
+    }
+
+    /// Adds the body or moves it to its new bounds. Only the cells it entered or left are touched.
+    pub fn update(&mut self, entity: Entity, bounds: Rectangle, layer: i32) {
+        let min_cell = self.get_cell(bounds.get_left(), bounds.get_top());
+        let max_cell = self.get_cell(bounds.get_right(), bounds.get_bottom());
+        let previous = self.bodies.insert(entity, SpatialHashBody {
+            bounds: bounds,
+            layer: layer,
+            min_cell: min_cell,
+            max_cell: max_cell,
+        });
+        match previous {
+            Some(previous) => {
+                if previous.min_cell == min_cell && previous.max_cell == max_cell {
+                    return;
+                }
+                for cell in cells_between(previous.min_cell, previous.max_cell) {
+                    if !in_cells(cell, min_cell, max_cell) {
+                        self.remove_from_cell(cell, entity);
+                    }
+                }
+                for cell in cells_between(min_cell, max_cell) {
+                    if !in_cells(cell, previous.min_cell, previous.max_cell) {
+                        self.cells.entry(cell).or_insert_with(Vec::new).push(entity);
+                    }
+                }
+            },
+            None => {
+                for cell in cells_between(min_cell, max_cell) {
+                    self.cells.entry(cell).or_insert_with(Vec::new).push(entity);
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        match self.bodies.remove(&entity) {
+            Some(body) => {
+                for cell in cells_between(body.min_cell, body.max_cell) {
+                    self.remove_from_cell(cell, entity);
+                }
+            },
+            None => {}
+        }
+    }
+
+    fn remove_from_cell(&mut self, cell: (i32, i32), entity: Entity) {
+        let empty = match self.cells.get_mut(&cell) {
+            Some(entities) => {
+                entities.retain(|e| *e != entity);
+                entities.is_empty()
+            },
+            None => false
+        };
+        if empty {
+            self.cells.remove(&cell);
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.bodies.contains_key(&entity)
+    }
+
+    pub fn get_bounds(&self, entity: Entity) -> Option<Rectangle> {
+        self.bodies.get(&entity).map(|body| body.bounds)
+    }
+
+    pub fn get_layer(&self, entity: Entity) -> Option<i32> {
+        self.bodies.get(&entity).map(|body| body.layer)
+    }
+
+    /// Every body in the hash.
+    pub fn get_entities(&self) -> Vec<Entity> {
+        self.bodies.keys().cloned().collect()
+    }
+
+    /// The bodies whose layer shares a bit with `layer_mask` (-1 for all of them) and whose
+    /// bounds overlap the area, each one once.
+    pub fn query_split(&self, x: f32, y: f32, w: f32, h: f32, layer_mask: i32) -> Vec<Entity> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        let min_cell = self.get_cell(x, y);
+        let max_cell = self.get_cell(x + w, y + h);
+        for cell in cells_between(min_cell, max_cell) {
+            let entities = match self.cells.get(&cell) {
+                Some(entities) => entities,
+                None => continue
+            };
+            for entity in entities {
+                if !seen.insert(*entity) {
+                    continue;
+                }
+                let body = &self.bodies[entity];
+                if body.layer & layer_mask == 0 {
+                    continue;
+                }
+                let bounds = body.bounds;
+                if bounds.get_left() < x + w && bounds.get_right() > x &&
+                   bounds.get_top() < y + h && bounds.get_bottom() > y {
+                    found.push(*entity);
+                }
+            }
+        }
+        found
+    }
+
+    pub fn query_rect(&self, rect: Rectangle, layer_mask: i32) -> Vec<Entity> {
+        self.query_split(rect.x, rect.y, rect.w as f32, rect.h as f32, layer_mask)
+    }
+
+    /// The bodies stored in the cells the segment goes through, in the order the cells are
+    /// crossed. They still have to be tested against the segment itself.
+    pub fn query_segment(&self, x1: f32, y1: f32, x2: f32, y2: f32, layer_mask: i32) -> Vec<Entity> {
+        if !(x1.is_finite() && y1.is_finite() && x2.is_finite() && y2.is_finite()) {
+            Log::warning("Cannot query the spatial hash with a segment that isn't finite");
+            return Vec::new();
+        }
+        match self.get_occupied_bounds() {
+            Some(bounds) => self.query_segment_in(x1, y1, x2, y2, bounds, layer_mask),
+            None => Vec::new()
+        }
+    }
+
+    /// Like `query_segment`, for the ray going from `origin` along the normalized `direction`.
+    /// `max_distance` can be infinite, the ray then stops once it has crossed every body.
+    pub fn query_ray(&self, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32, layer_mask: i32) -> Vec<Entity> {
+        if max_distance.is_nan() || max_distance < 0.0 {
+            return Vec::new();
+        }
+        let bounds = match self.get_occupied_bounds() {
+            Some(bounds) => bounds,
+            None => return Vec::new()
+        };
+        // past the farthest corner of the occupied cells there is nothing left to hit
+        let mut reach: f32 = 0.0;
+        for &(x, y) in [(bounds.0, bounds.1), (bounds.2, bounds.1), (bounds.0, bounds.3), (bounds.2, bounds.3)].iter() {
+            reach = reach.max(((x - origin.x) * (x - origin.x) + (y - origin.y) * (y - origin.y)).sqrt());
+        }
+        let end = origin + direction * max_distance.min(reach + self.cell_size);
+        if !(origin.x.is_finite() && origin.y.is_finite() && end.x.is_finite() && end.y.is_finite()) {
+            Log::warning("Cannot query the spatial hash with a ray that isn't finite");
+            return Vec::new();
+        }
+        self.query_segment_in(origin.x, origin.y, end.x, end.y, bounds, layer_mask)
+    }
+
+    /// Left, top, right and bottom of the cells holding bodies, None if there are none.
+    fn get_occupied_bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        let mut cells = self.cells.keys();
+        let first = *cells.next()?;
+        let (min_cell, max_cell) = cells.fold((first, first), |(min_cell, max_cell), cell| {
+            ((min_cell.0.min(cell.0), min_cell.1.min(cell.1)), (max_cell.0.max(cell.0), max_cell.1.max(cell.1)))
+        });
+        Some((min_cell.0 as f32 * self.cell_size, min_cell.1 as f32 * self.cell_size,
+              (max_cell.0 + 1) as f32 * self.cell_size, (max_cell.1 + 1) as f32 * self.cell_size))
+    }
+
+    fn query_segment_in(&self, x1: f32, y1: f32, x2: f32, y2: f32, bounds: (f32, f32, f32, f32), layer_mask: i32) -> Vec<Entity> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        // only walk the part of the segment over the occupied cells, in f64 so that the ends
+        // of long segments stay precise
+        let (x1, y1, x2, y2) = (x1 as f64, y1 as f64, x2 as f64, y2 as f64);
+        let (t0, t1) = match clip_segment(x1, y1, x2 - x1, y2 - y1, bounds) {
+            Some(range) => range,
+            None => return found
+        };
+        let (x1, y1, x2, y2) = ((x1 + (x2 - x1) * t0) as f32, (y1 + (y2 - y1) * t0) as f32,
+                                (x1 + (x2 - x1) * t1) as f32, (y1 + (y2 - y1) * t1) as f32);
+        let (mut cx, mut cy) = self.get_cell(x1, y1);
+        let end = self.get_cell(x2, y2);
+        let dx = x2 - x1;
+        let dy = y2 - y1;
+        let step_x = if dx > 0.0 { 1 } else { -1 };
+        let step_y = if dy > 0.0 { 1 } else { -1 };
+        // fraction of the segment at which the next vertical and horizontal cell borders are crossed
+        let mut t_x = self.get_next_border(cx, step_x, x1, dx);
+        let mut t_y = self.get_next_border(cy, step_y, y1, dy);
+        let t_delta_x = if dx == 0.0 { ::std::f32::INFINITY } else { self.cell_size / dx.abs() };
+        let t_delta_y = if dy == 0.0 { ::std::f32::INFINITY } else { self.cell_size / dy.abs() };
+        // each step moves to a neighbouring cell, rounding errors can't make the walk any longer
+        let max_steps = (end.0 - cx).abs() + (end.1 - cy).abs() + 2;
+        let mut steps = 0;
+        loop {
+            match self.cells.get(&(cx, cy)) {
+                Some(entities) => {
+                    for entity in entities {
+                        if self.bodies[entity].layer & layer_mask != 0 && seen.insert(*entity) {
+                            found.push(*entity);
+                        }
+                    }
+                },
+                None => {}
+            }
+            steps += 1;
+            if (cx, cy) == end || (t_x > 1.0 && t_y > 1.0) || steps > max_steps {
+                break;
+            }
+            if t_x < t_y {
+                cx += step_x;
+                t_x += t_delta_x;
+            } else {
+                cy += step_y;
+                t_y += t_delta_y;
+            }
+        }
+        found
+    }
+
+    fn get_next_border(&self, cell: i32, step: i32, start: f32, delta: f32) -> f32 {
+        if delta == 0.0 {
+            return ::std::f32::INFINITY;
+        }
+        let border = (cell + if step > 0 { 1 } else { 0 }) as f32 * self.cell_size;
+        (border - start) / delta
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.bodies.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+}
+
+fn in_cells(cell: (i32, i32), min_cell: (i32, i32), max_cell: (i32, i32)) -> bool {
+    cell.0 >= min_cell.0 && cell.0 <= max_cell.0 && cell.1 >= min_cell.1 && cell.1 <= max_cell.1
+}
+
+/// Range of the segment, as fractions of its length, inside the bounds (Liang-Barsky).
+fn clip_segment(x: f64, y: f64, dx: f64, dy: f64, bounds: (f32, f32, f32, f32)) -> Option<(f64, f64)> {
+    let (left, top, right, bottom) = (bounds.0 as f64, bounds.1 as f64, bounds.2 as f64, bounds.3 as f64);
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+    for &(p, q) in [(-dx, x - left), (dx, right - x), (-dy, y - top), (dy, bottom - y)].iter() {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0.0 {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+    Some((t0, t1))
+}
+
+fn cells_between(min_cell: (i32, i32), max_cell: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut cells = Vec::new();
+    for y in min_cell.1..max_cell.1 + 1 {
+        for x in min_cell.0..max_cell.0 + 1 {
+            cells.push((x, y));
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use entity::Entity;
+    use rectangle::Rectangle;
+    use spatialhash::{clip_segment, SpatialHash};
+    use super::cgmath::Vector2;
+
+    fn cells(hash: &SpatialHash) -> Vec<(i32, i32)> {
+        let mut cells: Vec<(i32, i32)> = hash.cells.keys().cloned().collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn bodies_move_between_cells() {
+        let mut hash = SpatialHash::new(10);
+        let a = Entity::new(0, 0);
+        hash.update(a, Rectangle::new(1.0, 1.0, 4, 4), 1);
+        assert_eq!(cells(&hash), vec![(0, 0)]);
+        hash.update(a, Rectangle::new(25.0, 1.0, 4, 4), 1);
+        assert_eq!(cells(&hash), vec![(2, 0)]);
+        hash.update(a, Rectangle::new(28.0, 1.0, 4, 4), 1);
+        assert_eq!(cells(&hash), vec![(2, 0), (3, 0)]);
+        assert_eq!(hash.cells[&(3, 0)], vec![a]);
+        assert!(hash.query_split(0.0, 0.0, 10.0, 10.0, -1).is_empty());
+        assert_eq!(hash.query_split(30.0, 0.0, 10.0, 10.0, -1), vec![a]);
+    }
+
+    #[test]
+    fn removing_bodies_drops_the_empty_cells() {
+        let mut hash = SpatialHash::new(10);
+        let a = Entity::new(0, 0);
+        let b = Entity::new(1, 0);
+        hash.update(a, Rectangle::new(1.0, 1.0, 4, 4), 1);
+        hash.update(b, Rectangle::new(5.0, 1.0, 10, 4), 1);
+        hash.remove(a);
+        assert_eq!(cells(&hash), vec![(0, 0), (1, 0)]);
+        assert_eq!(hash.cells[&(0, 0)], vec![b]);
+        hash.remove(b);
+        assert!(hash.cells.is_empty());
+        assert_eq!(hash.len(), 0);
+        // removing a body that isn't there does nothing
+        hash.remove(b);
+    }
+
+    #[test]
+    fn queries_return_each_body_once_and_filter_layers() {
+        let mut hash = SpatialHash::new(10);
+        let a = Entity::new(0, 0);
+        let b = Entity::new(1, 0);
+        hash.update(a, Rectangle::new(5.0, 5.0, 30, 30), 1);
+        hash.update(b, Rectangle::new(12.0, 12.0, 4, 4), 6);
+        assert_eq!(cells(&hash).len(), 16);
+        let mut found = hash.query_split(0.0, 0.0, 40.0, 40.0, -1);
+        found.sort_by_key(|e| e.get_index());
+        assert_eq!(found, vec![a, b]);
+        assert_eq!(hash.query_split(0.0, 0.0, 40.0, 40.0, 1), vec![a]);
+        assert_eq!(hash.query_split(0.0, 0.0, 40.0, 40.0, 4), vec![b]);
+        assert!(hash.query_split(0.0, 0.0, 40.0, 40.0, 8).is_empty());
+        // in the cells of a but outside its bounds
+        assert!(hash.query_split(0.0, 0.0, 4.0, 4.0, -1).is_empty());
+    }
+
+    #[test]
+    fn negative_coordinates_round_down() {
+        let mut hash = SpatialHash::new(10);
+        let a = Entity::new(0, 0);
+        hash.update(a, Rectangle::new(-15.0, -5.0, 4, 4), 1);
+        assert_eq!(cells(&hash), vec![(-2, -1)]);
+        assert_eq!(hash.query_split(-20.0, -10.0, 10.0, 10.0, -1), vec![a]);
+        assert!(hash.query_split(-5.0, -5.0, 4.0, 4.0, -1).is_empty());
+        hash.update(a, Rectangle::new(-2.0, -2.0, 4, 4), 1);
+        assert_eq!(cells(&hash), vec![(-1, -1), (-1, 0), (0, -1), (0, 0)]);
+    }
+
+    fn ray_hash() -> (SpatialHash, Entity, Entity, Entity) {
+        let mut hash = SpatialHash::new(10);
+        let (a, b, c) = (Entity::new(0, 0), Entity::new(1, 0), Entity::new(2, 0));
+        hash.update(a, Rectangle::new(25.0, 5.0, 4, 4), 1);
+        hash.update(b, Rectangle::new(5.0, 5.0, 4, 4), 1);
+        hash.update(c, Rectangle::new(5.0, 35.0, 4, 4), 2);
+        (hash, a, b, c)
+    }
+
+    #[test]
+    fn segments_return_the_bodies_in_the_order_the_cells_are_crossed() {
+        let (hash, a, b, c) = ray_hash();
+        assert_eq!(hash.query_segment(0.0, 8.0, 40.0, 8.0, -1), vec![b, a]);
+        assert_eq!(hash.query_segment(40.0, 8.0, 0.0, 8.0, -1), vec![a, b]);
+        assert_eq!(hash.query_segment(7.0, -50.0, 7.0, 50.0, -1), vec![b, c]);
+        assert_eq!(hash.query_segment(7.0, -50.0, 7.0, 50.0, 2), vec![c]);
+        assert_eq!(hash.query_segment(0.0, 0.0, 39.0, 39.0, -1), vec![b]);
+        assert_eq!(hash.query_segment(0.0, 8.0, 15.0, 8.0, -1), vec![b]);
+        assert!(hash.query_segment(0.0, 8.0, ::std::f32::INFINITY, 8.0, -1).is_empty());
+        assert!(SpatialHash::new(10).query_segment(0.0, 8.0, 40.0, 8.0, -1).is_empty());
+    }
+
+    #[test]
+    fn segments_are_clipped_to_the_occupied_cells() {
+        let bounds = (0.0, 0.0, 30.0, 10.0);
+        assert_eq!(clip_segment(-10.0, 5.0, 40.0, 0.0, bounds), Some((0.25, 1.0)));
+        assert_eq!(clip_segment(5.0, -10.0, 0.0, 40.0, bounds), Some((0.25, 0.5)));
+        assert_eq!(clip_segment(-10.0, 20.0, 40.0, 0.0, bounds), None);
+        assert_eq!(clip_segment(40.0, 5.0, 10.0, 0.0, bounds), None);
+        // a segment far from the bodies is only walked over their cells
+        let (hash, a, b, _) = ray_hash();
+        assert_eq!(hash.query_segment_in(-1.0e6, 8.0, 1.0e6, 8.0, (0.0, 0.0, 40.0, 40.0), -1), vec![b, a]);
+    }
+
+    #[test]
+    fn rays_stop_past_the_occupied_cells() {
+        let (hash, a, b, c) = ray_hash();
+        let right = Vector2::new(1.0, 0.0);
+        let infinity = ::std::f32::INFINITY;
+        assert_eq!(hash.query_ray(Vector2::new(-1.0e5, 8.0), right, infinity, -1), vec![b, a]);
+        assert_eq!(hash.query_ray(Vector2::new(0.0, 8.0), right, 15.0, -1), vec![b]);
+        assert_eq!(hash.query_ray(Vector2::new(7.0, 1.0e5), Vector2::new(0.0, -1.0), infinity, -1), vec![c, b]);
+        assert!(hash.query_ray(Vector2::new(0.0, 100.0), right, infinity, -1).is_empty());
+        assert!(hash.query_ray(Vector2::new(100.0, 8.0), right, infinity, -1).is_empty());
+        assert!(hash.query_ray(Vector2::new(0.0, 8.0), right, ::std::f32::NAN, -1).is_empty());
+    }
+}
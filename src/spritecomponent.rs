@@ -2,11 +2,18 @@ extern crate cgmath;
 
 use color::Color;
 use component::{Component};
+use storage::DenseVecStorage;
 use rectangle::Rectangle;
 use texture::Texture;
 use spritebatch::SpriteBatch;
 use subtexture::Subtexture;
-use entity::Entity;
+use transformcomponent::GlobalTransformComponent;
+use texturemanager::TextureManager;
+use atlas::atlasmanager::AtlasManager;
+use prefab;
+use prefab::PrefabComponent;
+use world::World;
+use serde_json::Value;
 use self::cgmath::Vector2;
 use std::collections::HashMap;
 use std::rc::Rc;
@@ -87,7 +94,52 @@ pub struct SpriteComponent {
 }
 
 impl Component for SpriteComponent {
-    type Storage = HashMap<usize, Self>;
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// `{"frames": [..]}` where a frame is either `{"atlas", "frame"}`, the name of a frame
+/// of an atlas in the AtlasManager resource, or `{"texture", "x", "y", "w", "h"}`, a region
+/// of a texture in the TextureManager resource.
+impl PrefabComponent for SpriteComponent {
+    fn get_prefab_name() -> &'static str {
+        "sprite"
+    }
+
+    fn from_prefab<T: 'static>(world: &World<T>, value: &Value) -> Option<Self> {
+        let frames = value.get("frames").and_then(|frames| frames.as_array())?;
+        let tm = world.resource::<TextureManager>()?;
+        let mut sc = SpriteComponent::new();
+        for frame in frames {
+            let (texture_id, rect) = match prefab::prefab_str(frame, "atlas") {
+                Some(atlas) => {
+                    let name = prefab::prefab_str(frame, "frame")?;
+                    let am = world.resource::<AtlasManager>()?;
+                    match am.get_frame(atlas, name) {
+                        Some((texture_id, rect)) => (String::from(texture_id), rect),
+                        None => {
+                            Log::error(&format!("Unknown frame {} in atlas {}", name, atlas));
+                            return None;
+                        }
+                    }
+                },
+                None => {
+                    let texture_id = prefab::prefab_str(frame, "texture")?;
+                    let rect = Rectangle::new(prefab::prefab_f32(frame, "x").unwrap_or(0.0), prefab::prefab_f32(frame, "y").unwrap_or(0.0),
+                                              prefab::prefab_f32(frame, "w")? as i32, prefab::prefab_f32(frame, "h")? as i32);
+                    (String::from(texture_id), rect)
+                }
+            };
+            let texture = match tm.find(&texture_id) {
+                Some(texture) => texture,
+                None => {
+                    Log::error(&format!("Unknown texture {}", texture_id));
+                    return None;
+                }
+            };
+            sc.add_frame_with_subtexture(Subtexture::with_texture(Some(texture), rect.x as i32, rect.y as i32, rect.w, rect.h));
+        }
+        Some(sc)
+    }
 }
 
 impl SpriteComponent {
@@ -125,26 +177,31 @@ impl SpriteComponent {
         self.add_frame(SpriteFrame::with_subtexture(subtexture));
     }
 
-    pub fn render_position(&self, entity: Option<&Entity>) -> Vector2<f32> {
-        match entity {
-            Some(_) => {
-                //return entity.unwrap().position + self.position;
-                // TODO: grab the position from the TransformComponent?
-                return Vector2::new(0.0, 0.0);
+    /// Position of the component in world space, given the transform of its entity.
+    pub fn render_position(&self, transform: Option<&GlobalTransformComponent>) -> Vector2<f32> {
+        match transform {
+            Some(transform) => {
+                return transform.transform_point(self.position);
             },
             None => {
-                return Vector2::new(0.0, 0.0);
+                return self.position;
             }
         }
     }
 
-    pub fn render(&self, entity: Option<&Entity>, spritebatch: &mut SpriteBatch) {
+    pub fn render(&self, transform: Option<&GlobalTransformComponent>, spritebatch: &mut SpriteBatch) {
         Log::info("Render called");
-        let render_pos = self.render_position(entity);
-        let scaled_vec = self.scale * self.zoom;
+        let render_pos = self.render_position(transform);
+        let (rotation, scaled_vec) = match transform {
+            Some(transform) => {
+                let scale = transform.get_scale();
+                (self.rotation + transform.get_rotation(), Vector2::new(self.scale.x * scale.x, self.scale.y * scale.y) * self.zoom)
+            },
+            None => (self.rotation, self.scale * self.zoom)
+        };
         let t = self.frames.get(self.current_frame).unwrap().subtexture.texture.as_ref().unwrap().clone();
         let clip_rect = self.frames.get(self.current_frame).unwrap().subtexture.get_rect();
-        spritebatch.draw_vector_scale(t, Some(render_pos), Some(*clip_rect), self.color, self.rotation,
+        spritebatch.draw_vector_scale(t, Some(render_pos), Some(*clip_rect), self.color, rotation,
         self.origin, scaled_vec, 0.0);
     }
 
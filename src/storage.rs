@@ -0,0 +1,308 @@
+use component::GenericStorage;
+use bit_set::BitSet;
+use std::mem;
+use std::vec::Vec;
+
+/// Storage backed by a vector indexed directly by entity index.
+/// Best suited to components that nearly every entity has, like the TransformComponent.
+#[derive(Clone)]
+pub struct VecStorage<T> {
+    data: Vec<Option<T>>,
+    count: usize,
+}
+
+impl<T> GenericStorage<T> for VecStorage<T> {
+    fn new() -> Self {
+        VecStorage {
+            data: Vec::new(),
+            count: 0,
+        }
+    }
+
+    fn insert(&mut self, index: usize, value: T) -> Option<T> {
+        while self.data.len() <= index {
+            self.data.push(None);
+        }
+        let old = mem::replace(&mut self.data[index], Some(value));
+        if old.is_none() {
+            self.count += 1;
+        }
+        old
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        match self.data.get(index) {
+            Some(value) => value.as_ref(),
+            None => None
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        match self.data.get_mut(index) {
+            Some(value) => value.as_mut(),
+            None => None
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.get(index).is_some()
+    }
+
+    fn indices(&self) -> Vec<usize> {
+        self.data.iter()
+            .enumerate()
+            .filter(|&(_, value)| value.is_some())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a> {
+        Box::new(self.data.iter()
+            .enumerate()
+            .filter_map(|(index, value)| value.as_ref().map(|value| (index, value))))
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = (usize, &'a mut T)> + 'a> {
+        Box::new(self.data.iter_mut()
+            .enumerate()
+            .filter_map(|(index, value)| value.as_mut().map(|value| (index, value))))
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        match self.data.get_mut(index) {
+            Some(value) => {
+                let old = value.take();
+                if old.is_some() {
+                    self.count -= 1;
+                }
+                old
+            },
+            None => None
+        }
+    }
+}
+
+/// Sparse set storage. Components are packed in a dense vector so iterating them
+/// doesn't touch the holes left by entities that don't have the component.
+#[derive(Clone)]
+pub struct DenseVecStorage<T> {
+    sparse: Vec<Option<usize>>,
+    dense: Vec<T>,
+    dense_indices: Vec<usize>,
+}
+
+impl<T> GenericStorage<T> for DenseVecStorage<T> {
+    fn new() -> Self {
+        DenseVecStorage {
+            sparse: Vec::new(),
+            dense: Vec::new(),
+            dense_indices: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, index: usize, value: T) -> Option<T> {
+        while self.sparse.len() <= index {
+            self.sparse.push(None);
+        }
+        match self.sparse[index] {
+            Some(dense_index) => {
+                Some(mem::replace(&mut self.dense[dense_index], value))
+            },
+            None => {
+                self.sparse[index] = Some(self.dense.len());
+                self.dense.push(value);
+                self.dense_indices.push(index);
+                None
+            }
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        match self.sparse.get(index) {
+            Some(&Some(dense_index)) => self.dense.get(dense_index),
+            _ => None
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        match self.sparse.get(index) {
+            Some(&Some(dense_index)) => self.dense.get_mut(dense_index),
+            _ => None
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        match self.sparse.get(index) {
+            Some(&Some(_)) => true,
+            _ => false
+        }
+    }
+
+    fn indices(&self) -> Vec<usize> {
+        self.dense_indices.clone()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a> {
+        Box::new(self.dense_indices.iter().cloned().zip(self.dense.iter()))
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = (usize, &'a mut T)> + 'a> {
+        Box::new(self.dense_indices.iter().cloned().zip(self.dense.iter_mut()))
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        let dense_index = match self.sparse.get_mut(index) {
+            Some(slot) => slot.take()?,
+            None => return None
+        };
+        // the last component takes the place of the removed one
+        let value = self.dense.swap_remove(dense_index);
+        self.dense_indices.swap_remove(dense_index);
+        if dense_index < self.dense_indices.len() {
+            let moved = self.dense_indices[dense_index];
+            self.sparse[moved] = Some(dense_index);
+        }
+        Some(value)
+    }
+}
+
+/// Storage for zero sized marker components. It only remembers which entities have
+/// the component and hands out the same instance to all of them.
+#[derive(Clone)]
+pub struct NullStorage<T> {
+    instance: T,
+    indices: BitSet,
+}
+
+impl<T: Default> GenericStorage<T> for NullStorage<T> {
+    fn new() -> Self {
+        assert!(mem::size_of::<T>() == 0, "NullStorage can only hold zero sized components");
+        NullStorage {
+            instance: T::default(),
+            indices: BitSet::new(),
+        }
+    }
+
+    fn insert(&mut self, index: usize, _value: T) -> Option<T> {
+        if self.indices.insert(index) {
+            None
+        } else {
+            Some(T::default())
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        if self.indices.contains(index) {
+            Some(&self.instance)
+        } else {
+            None
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if self.indices.contains(index) {
+            Some(&mut self.instance)
+        } else {
+            None
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.indices.contains(index)
+    }
+
+    fn indices(&self) -> Vec<usize> {
+        self.indices.iter().collect()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a> {
+        let instance = &self.instance;
+        Box::new(self.indices.iter().map(move |index| (index, instance)))
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = (usize, &'a mut T)> + 'a> {
+        // every entity gets the instance of the storage. It is zero sized (see `new`), so the
+        // references handed out can't overlap
+        let instance: *mut T = &mut self.instance;
+        Box::new(self.indices.iter().map(move |index| (index, unsafe { &mut *instance })))
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        if self.indices.remove(index) {
+            Some(T::default())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Debug, PartialEq)]
+    struct Marker;
+
+    #[test]
+    fn vec_storage() {
+        let mut storage: VecStorage<i32> = VecStorage::new();
+        assert_eq!(storage.insert(5, 1), None);
+        assert_eq!(storage.insert(5, 2), Some(1));
+        assert_eq!(storage.len(), 1);
+        assert_eq!(storage.get(5), Some(&2));
+        assert_eq!(storage.get(9), None);
+        assert_eq!(storage.remove(5), Some(2));
+        assert_eq!(storage.len(), 0);
+    }
+
+    #[test]
+    fn dense_vec_storage_moves_the_last_value_into_the_hole() {
+        let mut storage: DenseVecStorage<i32> = DenseVecStorage::new();
+        for i in 0..5 {
+            storage.insert(i * 2, i as i32);
+        }
+        assert_eq!(storage.remove(2), Some(1));
+        assert_eq!(storage.get(8), Some(&4));
+        assert_eq!(storage.get(2), None);
+        assert_eq!(storage.len(), 4);
+        let mut values: Vec<(usize, i32)> = storage.iter().map(|(index, value)| (index, *value)).collect();
+        values.sort();
+        assert_eq!(values, vec![(0, 0), (4, 2), (6, 3), (8, 4)]);
+        *storage.get_mut(8).unwrap() = 10;
+        assert_eq!(storage.get(8), Some(&10));
+    }
+
+    #[test]
+    fn null_storage_only_keeps_indices() {
+        let mut storage: NullStorage<Marker> = NullStorage::new();
+        assert_eq!(storage.insert(3, Marker), None);
+        assert_eq!(storage.insert(3, Marker), Some(Marker));
+        storage.insert(7, Marker);
+        assert!(storage.contains(3));
+        assert!(!storage.contains(4));
+        assert_eq!(storage.get(7), Some(&Marker));
+        let indices: Vec<usize> = storage.iter_mut().map(|(index, _)| index).collect();
+        assert_eq!(indices, vec![3, 7]);
+        assert_eq!(storage.remove(3), Some(Marker));
+        assert_eq!(storage.remove(3), None);
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn null_storage_rejects_sized_components() {
+        let _storage: NullStorage<i32> = NullStorage::new();
+    }
+}
@@ -0,0 +1,127 @@
+use entity::Entity;
+use std::vec::Vec;
+
+/// Tags are numbered from 0 to MAX_TAGS - 1 and stored as a bitmask on every entity.
+pub const MAX_TAGS: u32 = 32;
+
+pub fn tag_mask(tag: u32) -> u32 {
+    1 << tag
+}
+
+/// For every tag, the entities carrying it in the order they got it.
+#[derive(Clone)]
+pub struct TagLists {
+    lists: Vec<Vec<Entity>>,
+}
+
+impl TagLists {
+    pub fn new() -> Self {
+        TagLists {
+            lists: vec![Vec::new(); MAX_TAGS as usize],
+        }
+    }
+
+    pub fn add(&mut self, entity: Entity, tag: u32) {
+        let list = &mut self.lists[tag as usize];
+        if !list.contains(&entity) {
+            list.push(entity);
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity, tag: u32) {
+        self.lists[tag as usize].retain(|e| *e != entity);
+    }
+
+    /// Removes the entity from the list of every tag in the mask.
+    pub fn remove_entity(&mut self, entity: Entity, tags: u32) {
+        for tag in 0..MAX_TAGS {
+            if tags & tag_mask(tag) != 0 {
+                self.remove(entity, tag);
+            }
+        }
+    }
+
+    pub fn get(&self, tag: u32) -> &[Entity] {
+        &self.lists[tag as usize]
+    }
+}
+
+/// Selects entities by their tags: every tag of `include` must be there and none of `exclude`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TagFilter {
+    include: u32,
+    exclude: u32,
+}
+
+impl TagFilter {
+    /// Matches every entity.
+    pub fn new() -> Self {
+        TagFilter {
+            include: 0,
+            exclude: 0,
+        }
+    }
+
+    pub fn with_tag(mut self, tag: u32) -> Self {
+        self.include |= tag_mask(tag);
+        self
+    }
+
+    pub fn without_tag(mut self, tag: u32) -> Self {
+        self.exclude |= tag_mask(tag);
+        self
+    }
+
+    pub fn matches(&self, tags: u32) -> bool {
+        tags & self.include == self.include && tags & self.exclude == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commandbuffer::EntityBuilder;
+    use transformcomponent::TransformComponent;
+    use world::World;
+
+    #[test]
+    fn filters_match_required_and_excluded_tags() {
+        let filter = TagFilter::new().with_tag(1).without_tag(3);
+        assert!(filter.matches(tag_mask(1)));
+        assert!(filter.matches(tag_mask(1) | tag_mask(2)));
+        assert!(!filter.matches(tag_mask(1) | tag_mask(3)));
+        assert!(!filter.matches(tag_mask(2)));
+    }
+
+    #[test]
+    fn tag_lists_follow_the_entities() {
+        let mut world: World<()> = World::new();
+        world.register_component_with_storage::<TransformComponent>();
+        let a = world.create_entity();
+        let b = world.create_entity();
+        world.add_component_to_entity(a, TransformComponent::new());
+        world.add_component_to_entity(b, TransformComponent::new());
+        world.add_tag(b, 3);
+        world.add_tag(a, 3);
+        world.add_tag(a, 3);
+        world.add_tag(a, 5);
+        // out of range, ignored
+        world.add_tag(a, MAX_TAGS);
+        assert_eq!(world.get_entities_with_tag(3), &[b, a]);
+        assert_eq!(world.first_with_tag(3), Some(b));
+        assert_eq!(world.get_tags(a), tag_mask(3) | tag_mask(5));
+        assert_eq!(world.query::<&TransformComponent>().with_tag(3).without_tag(5).entities(), vec![b]);
+        assert_eq!(world.get_entities_matching(&TagFilter::new().with_tag(5)), vec![a]);
+        world.remove_tag(a, 5);
+        assert!(!world.has_tag(a, 5));
+        assert_eq!(world.count_with_tag(5), 0);
+        world.destroy_entity(b);
+        assert_eq!(world.get_entities_with_tag(3), &[a]);
+        // a reused slot doesn't keep the tags of the destroyed entity
+        let c = world.create_entity();
+        assert!(!world.has_tag(c, 3));
+        world.commands().spawn(EntityBuilder::new().with_tag(7));
+        world.apply_commands();
+        assert_eq!(world.count_with_tag(7), 1);
+    }
+}
@@ -0,0 +1,42 @@
+use renderer::Renderer;
+use scene::Scene;
+use spritebatch::SpriteBatch;
+use rectangle::Rectangle;
+use taglists::TagFilter;
+use imagecomponent::ImageComponent;
+use spritecomponent::SpriteComponent;
+use transformcomponent::GlobalTransformComponent;
+
+/// Draws the images and sprites of the entities whose tags match the filter,
+/// e.g. only the HUD or everything but the HUD, from the back to the front.
+pub struct TagRenderer {
+    filter: TagFilter,
+}
+
+impl TagRenderer {
+    pub fn new(filter: TagFilter) -> Self {
+        TagRenderer {
+            filter: filter,
+        }
+    }
+
+    pub fn get_filter(&self) -> &TagFilter {
+        &self.filter
+    }
+}
+
+impl<T: 'static> Renderer<T> for TagRenderer {
+    fn render_end <'sb>(&self, scene: &Scene<T>, viewport: Rectangle, spritebatch: &'sb mut SpriteBatch) {
+        let entities = scene.get_entities_matching(&self.filter);
+        for entity in scene.get_entities_in_draw_order(&entities) {
+            let transform = scene.get_component::<GlobalTransformComponent>(entity);
+            if let Some(image) = scene.get_component::<ImageComponent>(entity) {
+                image.render(transform, spritebatch);
+            }
+            if let Some(sprite) = scene.get_component::<SpriteComponent>(entity) {
+                sprite.render(transform, spritebatch);
+            }
+        }
+        spritebatch.end(viewport);
+    }
+}
@@ -88,4 +88,9 @@ impl TextureManager {
         entry.clone()
     }
 
+    /// Like `get` but returns None for unknown ids instead of panicking.
+    pub fn find(&self, id: &str) -> Option<Rc<Texture>> {
+        self.items.get(id).cloned()
+    }
+
 }
\ No newline at end of file
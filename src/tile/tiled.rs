@@ -1,5 +1,9 @@
+extern crate cgmath;
+
 use std::path::Path;
 use crate::utils;
+use crate::collider::PolygonCollider;
+use self::cgmath::Vector2;
 
 pub fn load_map_from_file(path: String) -> Option<tiled_json_rs::Map> {
     match utils::load_string_from_file(Path::new(&path)) {
@@ -11,4 +15,29 @@ pub fn load_map_from_file(path: String) -> Option<tiled_json_rs::Map> {
             return None;
         }
     }
-}
\ No newline at end of file
+}
+
+/// Builds a collider from an object of a Tiled object layer.
+/// Polygons keep their points and rectangles become boxes, both rotated like in Tiled.
+/// Ellipses, points, tiles and the other objects give None.
+pub fn polygon_collider_from_object(object: &tiled_json_rs::Object) -> Option<PolygonCollider> {
+    if object.gid.is_some() {
+        return None;
+    }
+    let points = match object.object_type {
+        tiled_json_rs::ObjectType::Polygon(ref polygon) => {
+            polygon.iter()
+                .map(|p| Vector2::new(p.x as f32, p.y as f32))
+                .collect::<Vec<_>>()
+        },
+        tiled_json_rs::ObjectType::None => {
+            let (width, height) = (object.width as f32, object.height as f32);
+            if width <= 0.0 || height <= 0.0 {
+                return None;
+            }
+            vec![Vector2::new(0.0, 0.0), Vector2::new(width, 0.0), Vector2::new(width, height), Vector2::new(0.0, height)]
+        },
+        _ => return None
+    };
+    PolygonCollider::from_tiled_polygon(object.x as f32, object.y as f32, object.rotation as f32, points)
+}
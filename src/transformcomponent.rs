@@ -1,15 +1,59 @@
 extern crate cgmath;
 
 use component::Component;
+use serialization::SerializableComponent;
+use prefab;
+use prefab::PrefabComponent;
+use world::World;
+use serde_json::Value;
+use storage::VecStorage;
 use self::cgmath::Vector2;
-use std::collections::HashMap;
 
+/// Position, rotation (in radians) and scale of an entity, relative to its parent if it has one.
+/// The origin is the point the entity rotates and scales around when it's drawn.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TransformComponent {
     position: Vector2<f32>,
+    rotation: f32,
+    scale: Vector2<f32>,
+    origin: Vector2<f32>,
 }
 
 impl Component for TransformComponent {
-    type Storage = HashMap<usize, Self>;
+    type Storage = VecStorage<Self>;
+}
+
+impl SerializableComponent for TransformComponent {
+    fn get_name() -> &'static str {
+        "transform"
+    }
+}
+
+/// `{"position": {"x", "y"}, "rotation", "scale": {"x", "y"}, "origin": {"x", "y"}}`, every field being optional.
+impl PrefabComponent for TransformComponent {
+    fn get_prefab_name() -> &'static str {
+        "transform"
+    }
+
+    fn from_prefab<T: 'static>(_world: &World<T>, value: &Value) -> Option<Self> {
+        if !value.is_object() {
+            return None;
+        }
+        let mut tc = TransformComponent::new();
+        if let Some(position) = prefab::prefab_vector(value, "position") {
+            tc.set_position(position.x, position.y);
+        }
+        if let Some(rotation) = prefab::prefab_f32(value, "rotation") {
+            tc.set_rotation(rotation);
+        }
+        if let Some(scale) = prefab::prefab_vector(value, "scale") {
+            tc.set_scale(scale.x, scale.y);
+        }
+        if let Some(origin) = prefab::prefab_vector(value, "origin") {
+            tc.set_origin(origin.x, origin.y);
+        }
+        Some(tc)
+    }
 }
 
 
@@ -17,6 +61,9 @@ impl TransformComponent {
     pub fn new() -> Self {
         TransformComponent {
             position: Vector2::new(0.0, 0.0),
+            rotation: 0.0,
+            scale: Vector2::new(1.0, 1.0),
+            origin: Vector2::new(0.0, 0.0),
         }
     }
 
@@ -28,4 +75,99 @@ impl TransformComponent {
         self.position.x = x;
         self.position.y = y;
     }
-}
\ No newline at end of file
+
+    pub fn get_rotation(&self) -> f32 {
+        return self.rotation;
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
+    pub fn get_scale(&self) -> &Vector2<f32> {
+        return &self.scale;
+    }
+
+    pub fn set_scale(&mut self, x: f32, y: f32) {
+        self.scale.x = x;
+        self.scale.y = y;
+    }
+
+    pub fn get_origin(&self) -> &Vector2<f32> {
+        return &self.origin;
+    }
+
+    pub fn set_origin(&mut self, x: f32, y: f32) {
+        self.origin.x = x;
+        self.origin.y = y;
+    }
+}
+
+/// World space transform of an entity, computed from its TransformComponent and the ones
+/// of its ancestors by `World::propagate_transforms`. Not meant to be written directly.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlobalTransformComponent {
+    position: Vector2<f32>,
+    rotation: f32,
+    scale: Vector2<f32>,
+    origin: Vector2<f32>,
+}
+
+impl Component for GlobalTransformComponent {
+    type Storage = VecStorage<Self>;
+}
+
+impl GlobalTransformComponent {
+    pub fn new() -> Self {
+        GlobalTransformComponent {
+            position: Vector2::new(0.0, 0.0),
+            rotation: 0.0,
+            scale: Vector2::new(1.0, 1.0),
+            origin: Vector2::new(0.0, 0.0),
+        }
+    }
+
+    /// The world transform of an entity without parent.
+    pub fn from_transform(transform: &TransformComponent) -> Self {
+        GlobalTransformComponent {
+            position: transform.position,
+            rotation: transform.rotation,
+            scale: transform.scale,
+            origin: transform.origin,
+        }
+    }
+
+    /// The world transform of a child with the given local transform.
+    pub fn combine(&self, transform: &TransformComponent) -> Self {
+        GlobalTransformComponent {
+            position: self.transform_point(transform.position),
+            rotation: self.rotation + transform.rotation,
+            scale: Vector2::new(self.scale.x * transform.scale.x, self.scale.y * transform.scale.y),
+            origin: transform.origin,
+        }
+    }
+
+    /// Converts a point from the local space of the entity to world space.
+    pub fn transform_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        let scaled = Vector2::new(point.x * self.scale.x, point.y * self.scale.y);
+        let (sin, cos) = self.rotation.sin_cos();
+        Vector2::new(self.position.x + scaled.x * cos - scaled.y * sin,
+                     self.position.y + scaled.x * sin + scaled.y * cos)
+    }
+
+    pub fn get_position(&self) -> &Vector2<f32> {
+        return &self.position;
+    }
+
+    pub fn get_rotation(&self) -> f32 {
+        return self.rotation;
+    }
+
+    pub fn get_scale(&self) -> &Vector2<f32> {
+        return &self.scale;
+    }
+
+    pub fn get_origin(&self) -> &Vector2<f32> {
+        return &self.origin;
+    }
+}
@@ -1,89 +1,342 @@
-use component::{Component, ComponentId, GenericStorage};
+use component::{AnyStorage, Component, ComponentHook, ComponentHooks, ComponentId, ComponentSnapshotter, ComponentStorage, GenericStorage};
 use entity::{Entity, EntityComponent, EntityData};
 use bit_set::BitSet;
+use commandbuffer::CommandBuffer;
 use std::boxed::Box;
 use std::rc::Rc;
 use std::vec::Vec;
 use std::collections::HashMap;
 use std::any::{Any, TypeId};
+use std::cell::{Cell, Ref, RefMut};
+use std::mem;
 use engine::Engine;
+use query::{Fetch, FetchMut, Query, QueryMut};
+use resources::Resources;
+use serialization::{ComponentSerializer, EntityMap, SavedEntity, SavedScene, SerializableComponent};
+use taglists::{tag_mask, TagFilter, TagLists, MAX_TAGS};
+use events::{AnyEvents, EventReader, Events};
+use log::Log;
+use serde_json;
+use std::collections::BTreeMap;
 use scene::Scene;
+use hierarchy::{ChildrenComponent, ParentComponent};
+use transformcomponent::{GlobalTransformComponent, TransformComponent};
+use prefab;
+use debugnamecomponentmanager::DebugNameComponentManager;
+use prefab::{Prefab, PrefabComponent, PrefabLibrary};
+use serde_json::Value;
 
 pub struct World<T> {
-    next_entity_id: Entity,
     entities: Vec<EntityData>,
+    free_entities: Vec<usize>,
+    active_entities: BitSet,
+    components: HashMap<TypeId, Box<dyn AnyStorage>>,
+    component_ids: HashMap<TypeId, ComponentId>,
+    component_hooks: HashMap<TypeId, ComponentHooks<T>>,
+    systems: Vec<SystemEntry<T>>,
+    system_order: Vec<usize>,
+    system_orderings: Vec<(SystemHandle, SystemHandle)>,
+    next_system_handle: usize,
+    commands: CommandBuffer<T>,
+    resources: Resources,
+    events: HashMap<TypeId, Box<dyn AnyEvents>>,
+    tag_lists: TagLists,
+    serializers: Vec<ComponentSerializer<T>>,
+    prefabs: PrefabLibrary<T>,
+    names: DebugNameComponentManager,
+    snapshotters: HashMap<TypeId, ComponentSnapshotter>,
+    change_tick: Cell<u32>,
+    last_run_tick: Cell<u32>,
+    frame_tick: u32,
+}
+
+/// State of a World taken by `World::snapshot`, to go back to it with `World::restore`.
+/// Only holds the components registered with `World::register_snapshot_component`.
+pub struct WorldSnapshot {
+    entities: Vec<EntityData>,
+    free_entities: Vec<usize>,
     active_entities: BitSet,
     components: HashMap<TypeId, Box<dyn Any>>,
-    systems: Vec<Box<dyn System<T>>>,
+    tag_lists: TagLists,
+    names: DebugNameComponentManager,
+    systems: HashMap<SystemHandle, (Vec<Entity>, u32)>,
+    change_tick: u32,
+    frame_tick: u32,
 }
 
-impl<T> World<T> {
+/// Groups of systems, run in this order every frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Stage {
+    PreUpdate,
+    Update,
+    PostUpdate,
+    Render,
+}
+
+impl Stage {
+    pub fn all() -> [Stage; 4] {
+        [Stage::PreUpdate, Stage::Update, Stage::PostUpdate, Stage::Render]
+    }
+}
+
+/// Identifies a system added to a World. Handles are never reused.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SystemHandle(usize);
+
+struct SystemEntry<T> {
+    handle: SystemHandle,
+    stage: Stage,
+    enabled: bool,
+    system: Box<dyn System<T>>,
+    signature: BitSet,
+    last_run: Cell<u32>,
+}
+
+impl<T: 'static> World<T> {
     pub fn new() -> Self {
-        World {
-            next_entity_id: 0,
+        let mut world = World {
             entities: Vec::new(),
+            free_entities: Vec::new(),
             active_entities: BitSet::new(),
             components: HashMap::new(),
+            component_ids: HashMap::new(),
+            component_hooks: HashMap::new(),
             systems: Vec::new(),
-        }
+            system_order: Vec::new(),
+            system_orderings: Vec::new(),
+            next_system_handle: 0,
+            commands: CommandBuffer::new(),
+            resources: Resources::new(),
+            events: HashMap::new(),
+            tag_lists: TagLists::new(),
+            serializers: Vec::new(),
+            prefabs: PrefabLibrary::new(),
+            names: DebugNameComponentManager::new(),
+            snapshotters: HashMap::new(),
+            change_tick: Cell::new(1),
+            last_run_tick: Cell::new(0),
+            frame_tick: 0,
+        };
+        world.register_component_with_storage::<ParentComponent>();
+        world.register_component_with_storage::<ChildrenComponent>();
+        world.register_serializable_component::<ParentComponent>();
+        world.register_snapshot_component::<ParentComponent>();
+        world.register_snapshot_component::<ChildrenComponent>();
+        world
     }
 
     pub fn create_entity(&mut self) -> Entity {
-        let e = self.next_entity_id;
-        self.entities.push(EntityData::new());
-        self.active_entities.insert(e);
-        self.next_entity_id += 1;
-        return e;
+        let index = match self.free_entities.pop() {
+            Some(index) => {
+                self.entities[index].revive();
+                index
+            },
+            None => {
+                self.entities.push(EntityData::new());
+                self.entities.len() - 1
+            }
+        };
+        self.active_entities.insert(index);
+        let entity = Entity::new(index, self.entities[index].get_generation());
+        self.update_system_membership(entity);
+        return entity;
+    }
+
+    /// Returns true if the handle still points at a living entity.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        match self.entities.get(entity.get_index()) {
+            Some(entity_data) => {
+                entity_data.is_alive() && entity_data.get_generation() == entity.get_generation()
+            },
+            None => false
+        }
+    }
+
+    /// Returns the handle of the entity currently living in the given slot, if any.
+    pub fn get_entity(&self, index: usize) -> Option<Entity> {
+        match self.entities.get(index) {
+            Some(entity_data) if entity_data.is_alive() => {
+                Some(Entity::new(index, entity_data.get_generation()))
+            },
+            _ => None
+        }
+    }
+
+    pub fn get_entities(&self) -> Vec<Entity> {
+        self.active_entities.iter()
+            .map(|index| Entity::new(index, self.entities[index].get_generation()))
+            .collect()
+    }
+
+    fn get_entity_data(&self, entity: Entity) -> Option<&EntityData> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.entities.get(entity.get_index())
+    }
+
+    fn get_entity_data_mut(&mut self, entity: Entity) -> Option<&mut EntityData> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.entities.get_mut(entity.get_index())
     }
 
     pub fn register_component_with_storage<C: Component>(&mut self) {
-        self.components.insert(TypeId::of::<C>(), Box::new(C::Storage::new()));
+        self.components.insert(TypeId::of::<C>(), Box::new(ComponentStorage::<C>::new()));
+        self.component_hooks.insert(TypeId::of::<C>(), ComponentHooks::of::<C>());
+        self.get_component_id(TypeId::of::<C>());
+    }
+
+    /// Runs the hook picked by `select` for each component the entity has.
+    /// The component types are collected first so hooks are free to change the entity.
+    fn run_component_hooks<F: Fn(&ComponentHooks<T>) -> ComponentHook<T>>(&mut self, entity: Entity, select: F) {
+        let component_types: Vec<TypeId> = match self.get_entity_data(entity) {
+            Some(entity_data) => {
+                entity_data.get_components().iter()
+                    .map(|ec| *ec.get_component_type())
+                    .collect()
+            },
+            None => return
+        };
+        for component_type in component_types {
+            if !self.is_alive(entity) {
+                return;
+            }
+            let hook = match self.component_hooks.get(&component_type) {
+                Some(hooks) => select(hooks),
+                None => continue
+            };
+            hook(self, entity);
+        }
+    }
+
+    /// Runs the `on_scene_begin` hooks of every living entity.
+    pub fn scene_begin(&mut self) {
+        for entity in self.get_entities() {
+            self.run_component_hooks(entity, |hooks| hooks.on_scene_begin);
+        }
+    }
+
+    /// Runs the `on_scene_end` hooks of every living entity.
+    pub fn scene_end(&mut self) {
+        for entity in self.get_entities() {
+            self.run_component_hooks(entity, |hooks| hooks.on_scene_end);
+        }
+    }
+
+    /// Returns the bit used for the component type in entity masks and system signatures,
+    /// assigning a new one the first time a type is seen.
+    pub fn get_component_id(&mut self, component_type: TypeId) -> ComponentId {
+        let next_id = self.component_ids.len();
+        *self.component_ids.entry(component_type).or_insert(next_id)
+    }
+
+    /// Adds the entity to every system whose signature its components match
+    /// and removes it from the ones it doesn't match anymore.
+    fn update_system_membership(&mut self, entity: Entity) {
+        let mask = self.entities[entity.get_index()].get_mask();
+        for entry in &mut self.systems {
+            if entry.signature.is_subset(mask) {
+                entry.system.add_entity(entity);
+            } else {
+                entry.system.remove_entity(entity);
+            }
+        }
+    }
+
+    fn get_component_storage<C: Component>(&self) -> &ComponentStorage<C> {
+        self.components[&TypeId::of::<C>()]
+            .as_any()
+            .downcast_ref::<ComponentStorage<C>>()
+            .unwrap()
+    }
+
+    fn get_component_storage_mut<C: Component>(&mut self) -> &mut ComponentStorage<C> {
+        self.components.get_mut(&TypeId::of::<C>())
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<ComponentStorage<C>>()
+            .unwrap()
+    }
+
+    fn get_storage<C: Component>(&self) -> &C::Storage {
+        self.get_component_storage::<C>().get_storage()
     }
     
     pub fn add_component_to_storage<C: Component>(&mut self, entity: Entity, component: C) -> usize {
-        let storage = self.components.get_mut(&TypeId::of::<C>()).unwrap().downcast_mut::<C::Storage>().unwrap();
-        storage.insert(entity, component);
+        let tick = self.change_tick.get();
+        let storage = self.get_component_storage_mut::<C>();
+        storage.insert(entity.get_index(), component, tick);
         //return storage.len() - 1;
-        return entity;
+        return entity.get_index();
     }
     
-    fn get_component<C: Component>(&self, index: usize) -> &C {
-        let storage = self.components[&TypeId::of::<C>()]
-            .downcast_ref::<C::Storage>()
-            .unwrap();
+    fn get_component_at<C: Component>(&self, index: usize) -> &C {
+        let storage = self.get_component_storage::<C>();
         let component = storage.get(index);
         component.unwrap()
     }
 
+    fn get_component_at_mut<C: Component>(&mut self, index: usize) -> &mut C {
+        let tick = self.change_tick.get();
+        let storage = self.get_component_storage_mut::<C>();
+        let component = storage.get_mut(index, tick);
+        component.unwrap()
+    }
+
     pub fn add_component_to_entity<C: Component>(&mut self, entity: Entity, component: C) {
+        if !self.is_alive(entity) {
+            return;
+        }
         let index = self.add_component_to_storage(entity, component);
-        let entity_data = self.entities.get_mut(entity).unwrap();
-        let ec = EntityComponent::new::<C>(index);
-        entity_data.get_components_mut().push(ec);
-        for system in &mut self.systems {
-            for component in system.get_components() {
-                if component == TypeId::of::<C>() {
-                    println!("calling add_entity for {}", entity);
-                    system.add_entity(entity);
-                }
+        let component_id = self.get_component_id(TypeId::of::<C>());
+        let added = {
+            let entity_data = self.entities.get_mut(entity.get_index()).unwrap();
+            if !entity_data.get_mask().contains(component_id) {
+                let ec = EntityComponent::new::<C>(index);
+                entity_data.get_components_mut().push(ec);
+                entity_data.get_mask_mut().insert(component_id);
+                true
+            } else {
+                false
             }
+        };
+        self.update_system_membership(entity);
+        // replacing a component the entity already has doesn't count as adding it
+        if added {
+            C::on_added(self, entity);
         }
     }
     
     pub fn get_component_for_entity<C: Component>(&self, entity: Entity) -> Option<&C> {
-        let entity_data = &self.entities.get(entity);
-        for entity_component in entity_data.unwrap().get_components() {
+        let entity_data = self.get_entity_data(entity)?;
+        for entity_component in entity_data.get_components() {
             if entity_component.get_component_type() == &TypeId::of::<C>() {
                 let index = entity_component.get_component_index();
-                return Some(self.get_component::<C>(*index));
+                return Some(self.get_component_at::<C>(*index));
             }
         }
         return None;
     }
 
+    pub fn get_component_mut<C: Component>(&mut self, entity: Entity) -> Option<&mut C> {
+        let index = self.get_component_index_for_entity::<C>(entity)?;
+        return Some(self.get_component_at_mut::<C>(index));
+    }
+
+    /// Runs the closure on the entity's component in place. Returns None if the entity
+    /// is gone or doesn't have that component.
+    pub fn with_component_mut<C: Component, R, F: FnOnce(&mut C) -> R>(&mut self, entity: Entity, f: F) -> Option<R> {
+        match self.get_component_mut::<C>(entity) {
+            Some(component) => Some(f(component)),
+            None => None
+        }
+    }
+
     pub fn get_component_index_for_entity<C: Component>(&self, entity: Entity) -> Option<usize> {
-        let entity_data = &self.entities.get(entity);
-        for entity_component in entity_data.unwrap().get_components() {
+        let entity_data = self.get_entity_data(entity)?;
+        for entity_component in entity_data.get_components() {
             if entity_component.get_component_type() == &TypeId::of::<C>() {
                 return Some(*entity_component.get_component_index());
             }
@@ -91,47 +344,876 @@ impl<T> World<T> {
         return None;
     }
 
-    pub fn get_components_of_type<C: Component>(&self) -> &HashMap<usize, C> {
-        let storage = self.components[&TypeId::of::<C>()]
-            .downcast_ref::<C::Storage>()
-            .unwrap();
-        return storage.all();
+    pub fn get_components_of_type<C: Component>(&self) -> &C::Storage {
+        return self.get_storage::<C>();
     }
 
-    pub fn remove_component_from_storage<C: Component>(&mut self, component_index: usize) -> C {
-        let storage = self.components.get_mut(&TypeId::of::<C>()).unwrap().downcast_mut::<C::Storage>().unwrap();
-        return storage.remove(component_index).unwrap();
+    /// Every component of the type is marked as changed, since there is no telling which ones will be written.
+    pub fn get_components_of_type_mut<C: Component>(&mut self) -> &mut C::Storage {
+        let tick = self.change_tick.get();
+        let storage = self.get_component_storage_mut::<C>();
+        storage.mark_all_changed(tick);
+        return storage.get_storage_mut();
+    }
+
+    /// Tick stamped on the components added or borrowed mutably right now.
+    pub fn get_change_tick(&self) -> u32 {
+        self.change_tick.get()
+    }
+
+    /// Changes made after this tick are the ones the caller hasn't seen yet. While a system runs
+    /// that's the tick of its previous run, otherwise the tick of the last `clear_trackers` call.
+    pub fn get_last_run_tick(&self) -> u32 {
+        self.last_run_tick.get()
+    }
+
+    /// True if the entity got its C after `get_last_run_tick`.
+    pub fn is_added<C: Component>(&self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        match self.get_component_storage::<C>().get_ticks(entity.get_index()) {
+            Some(ticks) => ticks.is_added(self.last_run_tick.get()),
+            None => false
+        }
+    }
+
+    /// True if the entity's C was added or borrowed mutably after `get_last_run_tick`.
+    pub fn is_changed<C: Component>(&self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        match self.get_component_storage::<C>().get_ticks(entity.get_index()) {
+            Some(ticks) => ticks.is_changed(self.last_run_tick.get()),
+            None => false
+        }
+    }
+
+    /// Entities that lost their C, or were destroyed, after `get_last_run_tick`.
+    /// Removals are remembered until the second `clear_trackers` call after them.
+    pub fn removed_components<C: Component>(&self) -> Vec<Entity> {
+        let since = self.last_run_tick.get();
+        self.get_component_storage::<C>().get_removed().iter()
+            .filter(|&&(_, tick)| tick > since)
+            .map(|&(entity, _)| entity)
+            .collect()
+    }
+
+    /// Ends the current frame for change detection. Called once per frame.
+    pub fn clear_trackers(&mut self) {
+        let previous_frame_tick = self.frame_tick;
+        self.frame_tick = self.change_tick.get();
+        self.change_tick.set(self.frame_tick + 1);
+        self.last_run_tick.set(self.frame_tick);
+        for storage in self.components.values_mut() {
+            storage.clear_removed(previous_frame_tick);
+        }
+    }
+
+    /// Iterates every entity having the components in `Q`, e.g. `(&TransformComponent, Option<&ImageComponent>)`.
+    pub fn query<'a, Q: Fetch<'a>>(&'a self) -> Query<'a, Q> {
+        Query::new(&self.entities, &self.components, self.last_run_tick.get())
+    }
+
+    /// Like `query` but `Q` can also contain `&mut C` and `Option<&mut C>` elements.
+    /// The components borrowed mutably are marked as changed.
+    pub fn query_mut<Q>(&mut self) -> QueryMut<Q> where Q: for<'a> FetchMut<'a> {
+        QueryMut::new(&self.entities, &mut self.components, self.last_run_tick.get(), self.change_tick.get())
+    }
+
+    fn remove_component_from_storage<C: Component>(&mut self, entity: Entity) -> Option<C> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        let tick = self.change_tick.get();
+        let storage = self.get_component_storage_mut::<C>();
+        return storage.remove(entity, tick);
     }
 
     pub fn remove_component_from_entity<C: Component>(&mut self, entity: Entity) {
-        let index = self.get_component_index_for_entity::<C>(entity);
-        match index {
-            Some(index) => {
-                self.remove_component_from_storage::<C>(index);
-                self.entities.remove(entity);
+        if self.get_component_index_for_entity::<C>(entity).is_none() {
+            return;
+        }
+        C::on_removed(self, entity);
+        // the hook may have changed the entity, so look the component up again
+        let position = match self.get_entity_data(entity) {
+            Some(entity_data) => {
+                entity_data.get_components().iter()
+                    .position(|ec| ec.get_component_type() == &TypeId::of::<C>())
+            },
+            None => None
+        };
+        match position {
+            Some(position) => {
+                let component_id = self.get_component_id(TypeId::of::<C>());
+                self.entities[entity.get_index()].get_components_mut().remove(position);
+                self.entities[entity.get_index()].get_mask_mut().remove(component_id);
+                self.remove_component_from_storage::<C>(entity);
+                self.update_system_membership(entity);
             },
             None => {}
         }
     }
 
+    /// Lets the components of type C be saved and loaded. Registers their storage too if needed.
+    pub fn register_serializable_component<C: SerializableComponent>(&mut self) {
+        if !self.components.contains_key(&TypeId::of::<C>()) {
+            self.register_component_with_storage::<C>();
+        }
+        let name = C::get_name();
+        if self.serializers.iter().any(|serializer| serializer.get_name() == name) {
+            Log::warning(&format!("A serializable component named {} is already registered", name));
+            return;
+        }
+        self.serializers.push(ComponentSerializer::of::<C>());
+    }
+
+    /// Saves every living entity to JSON.
+    pub fn save(&self) -> String {
+        let entities = self.get_entities();
+        self.save_entities(&entities)
+    }
+
+    /// Saves the entities to JSON, with their tags and serializable components.
+    /// References to entities left out are dropped when loading.
+    pub fn save_entities(&self, entities: &[Entity]) -> String {
+        let mut saved = SavedScene {
+            entities: Vec::new(),
+        };
+        for entity in entities {
+            if !self.is_alive(*entity) {
+                continue;
+            }
+            let mut components = BTreeMap::new();
+            for serializer in &self.serializers {
+                match serializer.save(self, *entity) {
+                    Some(value) => {
+                        components.insert(String::from(serializer.get_name()), value);
+                    },
+                    None => {}
+                }
+            }
+            saved.entities.push(SavedEntity {
+                entity: *entity,
+                name: self.get_name(*entity).map(String::from),
+                tags: self.get_tags(*entity),
+                components: components,
+            });
+        }
+        serde_json::to_string_pretty(&saved).unwrap()
+    }
+
+    /// Creates the entities saved in the JSON, next to the existing ones, and returns them
+    /// in the order they were saved. Components of unknown types are skipped.
+    pub fn load(&mut self, json: &str) -> Option<Vec<Entity>> {
+        let saved: SavedScene = match serde_json::from_str(json) {
+            Ok(saved) => saved,
+            Err(e) => {
+                Log::error(&format!("Cannot load the scene: {}", e));
+                return None;
+            }
+        };
+        // every entity has to exist before the references between them can be remapped
+        let mut map = EntityMap::new();
+        let mut loaded = Vec::new();
+        for saved_entity in &saved.entities {
+            let entity = self.create_entity();
+            map.insert(saved_entity.entity, entity);
+            loaded.push(entity);
+        }
+        let serializers = self.serializers.clone();
+        for (saved_entity, entity) in saved.entities.into_iter().zip(loaded.iter()) {
+            if let Some(ref name) = saved_entity.name {
+                self.set_name(*entity, name);
+            }
+            for tag in 0..MAX_TAGS {
+                if saved_entity.tags & tag_mask(tag) != 0 {
+                    self.add_tag(*entity, tag);
+                }
+            }
+            for (name, value) in saved_entity.components {
+                match serializers.iter().find(|serializer| serializer.get_name() == name) {
+                    Some(serializer) => {
+                        serializer.load(self, *entity, value, &map);
+                    },
+                    None => {
+                        Log::warning(&format!("Skipping unknown component {}", name));
+                    }
+                }
+            }
+        }
+        Some(loaded)
+    }
+
+    /// Lets prefabs describe components of type C. Registers their storage too if needed.
+    pub fn register_prefab_component<C: PrefabComponent>(&mut self) {
+        if !self.components.contains_key(&TypeId::of::<C>()) {
+            self.register_component_with_storage::<C>();
+        }
+        self.prefabs.register_component::<C>();
+    }
+
+    /// Adds the prefab, replacing the one with the same name.
+    pub fn add_prefab(&mut self, name: &str, prefab: Prefab) {
+        self.prefabs.add(name, prefab);
+    }
+
+    /// Parses the prefab from JSON and adds it. Returns false if it isn't valid.
+    pub fn load_prefab(&mut self, name: &str, json: &str) -> bool {
+        self.prefabs.load(name, json)
+    }
+
+    pub fn has_prefab(&self, name: &str) -> bool {
+        self.prefabs.contains(name)
+    }
+
+    /// Creates an entity, and its children, from the prefab.
+    pub fn spawn_prefab(&mut self, name: &str) -> Option<Entity> {
+        self.spawn_prefab_with(name, Value::Null)
+    }
+
+    /// Like `spawn_prefab` with the components of the prefab overridden by `components`,
+    /// an object described like the `components` of a prefab file.
+    pub fn spawn_prefab_with(&mut self, name: &str, components: Value) -> Option<Entity> {
+        let mut prefab = Prefab::inheriting(name);
+        match components {
+            Value::Object(components) => {
+                prefab.components = components;
+            },
+            Value::Null => {},
+            _ => {
+                Log::error(&format!("The overrides of prefab {} have to be an object", name));
+                return None;
+            }
+        }
+        self.spawn_prefab_from(&prefab)
+    }
+
+    /// Like `spawn_prefab` with the entity placed at the given position.
+    pub fn spawn_prefab_at(&mut self, name: &str, x: f32, y: f32) -> Option<Entity> {
+        let prefab = Prefab::inheriting(name).with_component("transform", prefab::position_override(x, y));
+        self.spawn_prefab_from(&prefab)
+    }
+
+    /// Creates an entity from a prefab that isn't part of the library, it may inherit from one that is.
+    pub fn spawn_prefab_from(&mut self, prefab: &Prefab) -> Option<Entity> {
+        let resolved = self.prefabs.resolve(prefab)?;
+        let loaders = self.prefabs.get_loaders().to_vec();
+        prefab::instantiate(self, &resolved, &loaders)
+    }
+
+    /// Names the entity. Several entities can share a name, unless one of them got it
+    /// with `set_unique_name`. Returns false if the name is taken.
+    pub fn set_name(&mut self, entity: Entity, name: &str) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.rename(entity, name, false)
+    }
+
+    /// Names the entity, unless another entity already has the name. Returns false if it does.
+    pub fn set_unique_name(&mut self, entity: Entity, name: &str) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.rename(entity, name, true)
+    }
+
+    fn rename(&mut self, entity: Entity, name: &str, unique: bool) -> bool {
+        let had_name = self.names.get_entity_name(entity).is_some();
+        let instance = self.names.create(entity);
+        let renamed = match unique {
+            true => self.names.set_unique_name(instance, String::from(name)),
+            false => self.names.set_name(instance, String::from(name))
+        };
+        // don't leave the placeholder name of a new entry behind
+        if !renamed && !had_name {
+            self.names.remove(entity);
+        }
+        renamed
+    }
+
+    pub fn remove_name(&mut self, entity: Entity) {
+        self.names.remove(entity);
+    }
+
+    pub fn get_name(&self, entity: Entity) -> Option<&str> {
+        self.names.get_entity_name(entity)
+    }
+
+    /// The first entity that got the name.
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.names.find(name)
+    }
+
+    /// The entities with the name, in the order they got it.
+    pub fn find_all_by_name(&self, name: &str) -> &[Entity] {
+        self.names.find_all(name)
+    }
+
+    /// The name and id of the entity, to use in logs and debug overlays.
+    pub fn get_debug_label(&self, entity: Entity) -> String {
+        self.names.get_label(entity)
+    }
+
+    pub fn get_names(&self) -> &DebugNameComponentManager {
+        &self.names
+    }
+
+    /// Lets the components of type C be kept by snapshots. Registers their storage too if needed.
+    pub fn register_snapshot_component<C: Component>(&mut self) where C::Storage: Clone {
+        if !self.components.contains_key(&TypeId::of::<C>()) {
+            self.register_component_with_storage::<C>();
+        }
+        self.snapshotters.insert(TypeId::of::<C>(), ComponentSnapshotter::of::<C>());
+    }
+
+    /// Copies the entities, their tags and names and the components registered with
+    /// `register_snapshot_component`. Meant to be taken between frames, e.g. for rollback.
+    pub fn snapshot(&self) -> WorldSnapshot {
+        let mut components = HashMap::new();
+        for (component_type, snapshotter) in &self.snapshotters {
+            match self.components.get(component_type) {
+                Some(storage) => {
+                    components.insert(*component_type, snapshotter.capture(&**storage));
+                },
+                None => {}
+            }
+        }
+        let mut systems = HashMap::new();
+        for entry in &self.systems {
+            systems.insert(entry.handle, (entry.system.get_entities().to_vec(), entry.last_run.get()));
+        }
+        WorldSnapshot {
+            entities: self.entities.clone(),
+            free_entities: self.free_entities.clone(),
+            active_entities: self.active_entities.clone(),
+            components: components,
+            tag_lists: self.tag_lists.clone(),
+            names: self.names.clone(),
+            systems: systems,
+            change_tick: self.change_tick.get(),
+            frame_tick: self.frame_tick,
+        }
+    }
+
+    /// Brings the World back to the snapshot. Components that aren't kept by snapshots
+    /// stay on the entities that are still alive with the same generation, the others lose them.
+    /// Component hooks don't run, pending commands are dropped and resources and events
+    /// are left as they are.
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) {
+        let previous = mem::replace(&mut self.entities, snapshot.entities.clone());
+        self.free_entities = snapshot.free_entities.clone();
+        self.active_entities = snapshot.active_entities.clone();
+        self.tag_lists = snapshot.tag_lists.clone();
+        self.names = snapshot.names.clone();
+        self.change_tick.set(snapshot.change_tick);
+        self.frame_tick = snapshot.frame_tick;
+        self.last_run_tick.set(self.frame_tick);
+        let tick = self.change_tick.get();
+        let component_types: Vec<TypeId> = self.components.keys().cloned().collect();
+        for component_type in component_types {
+            let component_id = self.get_component_id(component_type);
+            let storage = self.components.get_mut(&component_type).unwrap();
+            match (self.snapshotters.get(&component_type), snapshot.components.get(&component_type)) {
+                (Some(snapshotter), Some(saved)) => {
+                    snapshotter.restore(&mut **storage, &**saved);
+                },
+                _ => {
+                    for index in storage.indices() {
+                        let before = match previous.get(index) {
+                            Some(entity_data) => entity_data.get_generation(),
+                            None => continue
+                        };
+                        match self.entities.get_mut(index) {
+                            Some(entity_data) if entity_data.is_alive() && entity_data.get_generation() == before => {
+                                if !entity_data.get_mask().contains(component_id) {
+                                    entity_data.get_components_mut().push(EntityComponent::with_type(component_type, index));
+                                    entity_data.get_mask_mut().insert(component_id);
+                                }
+                            },
+                            _ => {
+                                storage.remove_entity(Entity::new(index, before), tick);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // drop the components the snapshot knew about that aren't stored anymore
+        for (index, entity_data) in self.entities.iter_mut().enumerate() {
+            let mut lost = Vec::new();
+            for ec in entity_data.get_components() {
+                let stored = match self.components.get(ec.get_component_type()) {
+                    Some(storage) => storage.contains(index),
+                    None => false
+                };
+                if !stored {
+                    lost.push(*ec.get_component_type());
+                }
+            }
+            for component_type in lost {
+                entity_data.get_components_mut().retain(|ec| *ec.get_component_type() != component_type);
+                match self.component_ids.get(&component_type) {
+                    Some(component_id) => {
+                        entity_data.get_mask_mut().remove(*component_id);
+                    },
+                    None => {}
+                }
+            }
+        }
+        // systems go back to their previous entity order, so that they process them the same way
+        for entry in &mut self.systems {
+            for entity in entry.system.get_entities().to_vec() {
+                entry.system.remove_entity(entity);
+            }
+            match snapshot.systems.get(&entry.handle) {
+                Some(&(ref entities, last_run)) => {
+                    for entity in entities {
+                        entry.system.add_entity(*entity);
+                    }
+                    entry.last_run.set(last_run);
+                },
+                None => {}
+            }
+        }
+        for entity in self.get_entities() {
+            self.update_system_membership(entity);
+        }
+        self.commands.drain();
+    }
+
+    /// Tags the entity, `tag` going from 0 to `MAX_TAGS` - 1.
+    pub fn add_tag(&mut self, entity: Entity, tag: u32) {
+        if tag >= MAX_TAGS {
+            Log::warning("Tag out of range");
+            return;
+        }
+        match self.get_entity_data_mut(entity) {
+            Some(entity_data) => {
+                let tags = entity_data.get_tags();
+                entity_data.set_tags(tags | tag_mask(tag));
+            },
+            None => return
+        }
+        self.tag_lists.add(entity, tag);
+    }
+
+    pub fn remove_tag(&mut self, entity: Entity, tag: u32) {
+        if tag >= MAX_TAGS {
+            return;
+        }
+        match self.get_entity_data_mut(entity) {
+            Some(entity_data) => {
+                let tags = entity_data.get_tags();
+                entity_data.set_tags(tags & !tag_mask(tag));
+            },
+            None => return
+        }
+        self.tag_lists.remove(entity, tag);
+    }
+
+    pub fn has_tag(&self, entity: Entity, tag: u32) -> bool {
+        tag < MAX_TAGS && self.get_tags(entity) & tag_mask(tag) != 0
+    }
+
+    /// Bitmask of the tags of the entity, 0 if it's gone.
+    pub fn get_tags(&self, entity: Entity) -> u32 {
+        match self.get_entity_data(entity) {
+            Some(entity_data) => entity_data.get_tags(),
+            None => 0
+        }
+    }
+
+    /// The entities having the tag, in the order they were tagged.
+    pub fn get_entities_with_tag(&self, tag: u32) -> &[Entity] {
+        if tag >= MAX_TAGS {
+            return &[];
+        }
+        self.tag_lists.get(tag)
+    }
+
+    pub fn first_with_tag(&self, tag: u32) -> Option<Entity> {
+        self.get_entities_with_tag(tag).first().cloned()
+    }
+
+    pub fn count_with_tag(&self, tag: u32) -> usize {
+        self.get_entities_with_tag(tag).len()
+    }
+
+    /// The living entities whose tags match the filter.
+    pub fn get_entities_matching(&self, filter: &TagFilter) -> Vec<Entity> {
+        self.active_entities.iter()
+            .filter(|index| filter.matches(self.entities[*index].get_tags()))
+            .map(|index| Entity::new(index, self.entities[index].get_generation()))
+            .collect()
+    }
+
+    /// Attaches the child to the parent, detaching it from its previous parent first.
+    /// Ignored if the parent is the child itself or one of its descendants.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        if !self.is_alive(child) || !self.is_alive(parent) {
+            return;
+        }
+        let mut ancestor = Some(parent);
+        while let Some(entity) = ancestor {
+            if entity == child {
+                Log::warning(&format!("Cannot attach {} to {}: an entity can't be attached to itself or to one of its descendants",
+                    self.get_debug_label(child), self.get_debug_label(parent)));
+                return;
+            }
+            ancestor = self.get_parent(entity);
+        }
+        self.remove_parent(child);
+        self.add_component_to_entity(child, ParentComponent::new(parent));
+        if self.get_component_index_for_entity::<ChildrenComponent>(parent).is_none() {
+            self.add_component_to_entity(parent, ChildrenComponent::new());
+        }
+        self.get_component_mut::<ChildrenComponent>(parent).unwrap().add_child(child);
+    }
+
+    /// Detaches the entity from its parent, making it a root again.
+    pub fn remove_parent(&mut self, child: Entity) {
+        let parent = match self.get_parent(child) {
+            Some(parent) => parent,
+            None => return
+        };
+        self.remove_component_from_entity::<ParentComponent>(child);
+        let no_children_left = match self.get_component_mut::<ChildrenComponent>(parent) {
+            Some(children) => {
+                children.remove_child(child);
+                children.get_children().is_empty()
+            },
+            None => false
+        };
+        if no_children_left {
+            self.remove_component_from_entity::<ChildrenComponent>(parent);
+        }
+    }
+
+    pub fn get_parent(&self, entity: Entity) -> Option<Entity> {
+        match self.get_component_for_entity::<ParentComponent>(entity) {
+            Some(parent) => Some(parent.get_parent()),
+            None => None
+        }
+    }
+
+    pub fn get_children(&self, entity: Entity) -> Vec<Entity> {
+        match self.get_component_for_entity::<ChildrenComponent>(entity) {
+            Some(children) => children.get_children().to_vec(),
+            None => Vec::new()
+        }
+    }
+
+    /// Computes the GlobalTransformComponent of every entity having a TransformComponent,
+    /// parents before their children. Children without a TransformComponent pass the
+    /// transform of their parent down to their own children. Called once per frame.
+    pub fn propagate_transforms(&mut self) {
+        if !self.components.contains_key(&TypeId::of::<TransformComponent>()) {
+            return;
+        }
+        if !self.components.contains_key(&TypeId::of::<GlobalTransformComponent>()) {
+            self.register_component_with_storage::<GlobalTransformComponent>();
+        }
+        let mut roots = self.query::<&TransformComponent>().without::<ParentComponent>().entities();
+        roots.extend(self.query::<&ChildrenComponent>()
+            .without::<ParentComponent>()
+            .without::<TransformComponent>()
+            .entities());
+        roots.sort();
+
+        let mut pending: Vec<(Entity, Option<GlobalTransformComponent>)> = roots.into_iter()
+            .rev()
+            .map(|entity| (entity, None))
+            .collect();
+        while let Some((entity, parent_global)) = pending.pop() {
+            let global = match self.get_component_for_entity::<TransformComponent>(entity) {
+                Some(transform) => {
+                    match parent_global {
+                        Some(parent_global) => Some(parent_global.combine(transform)),
+                        None => Some(GlobalTransformComponent::from_transform(transform))
+                    }
+                },
+                None => None
+            };
+            match global {
+                Some(global) => {
+                    // only write on change so the change detection stays meaningful
+                    let current = self.get_component_for_entity::<GlobalTransformComponent>(entity).cloned();
+                    match current {
+                        Some(current) if current == global => {},
+                        Some(_) => {
+                            *self.get_component_mut::<GlobalTransformComponent>(entity).unwrap() = global;
+                        },
+                        None => {
+                            self.add_component_to_entity(entity, global);
+                        }
+                    }
+                },
+                None => {}
+            }
+            let inherited = match global {
+                Some(global) => Some(global),
+                None => parent_global
+            };
+            for child in self.get_children(entity).into_iter().rev() {
+                pending.push((child, inherited));
+            }
+        }
+    }
+
+    /// Removes every component of the entity from its storage, drops the entity from the
+    /// systems watching it and frees its slot for reuse. Its children are destroyed too.
     pub fn destroy_entity(&mut self, entity: Entity) {
-        for storage in &self.components {
+        if !self.is_alive(entity) {
+            return;
+        }
+        for child in self.get_children(entity) {
+            self.destroy_entity(child);
+        }
+        self.remove_parent(entity);
+        self.run_component_hooks(entity, |hooks| hooks.on_removed);
+        if !self.is_alive(entity) {
+            return;
+        }
+        let tick = self.change_tick.get();
+        for ec in self.entities[entity.get_index()].get_components() {
+            match self.components.get_mut(ec.get_component_type()) {
+                Some(storage) => {
+                    storage.remove_entity(entity, tick);
+                },
+                None => {}
+            }
+        }
+        for entry in &mut self.systems {
+            entry.system.remove_entity(entity);
         }
+        let tags = self.entities[entity.get_index()].get_tags();
+        self.tag_lists.remove_entity(entity, tags);
+        self.names.remove(entity);
+        self.entities[entity.get_index()].kill();
+        self.active_entities.remove(entity.get_index());
+        self.free_entities.push(entity.get_index());
     }
 
-    pub fn add_system<S: System<T>>(&mut self, system: S) -> usize {
-        self.systems.push(Box::new(system));
-        return self.systems.len() - 1;
+    /// Adds the system to the Update stage.
+    pub fn add_system<S: System<T>>(&mut self, system: S) -> SystemHandle {
+        self.add_system_to_stage(Stage::Update, system)
     }
 
-    pub fn process(&self, dt: f32, scene: &Scene<T>, user_data: &mut T) {
-        for system in &self.systems {
-            for entity in system.get_entities() {
-                println!("calling process");
-                system.process(entity, dt, scene, user_data);
+    pub fn add_system_to_stage<S: System<T>>(&mut self, stage: Stage, system: S) -> SystemHandle {
+        let mut signature = BitSet::new();
+        for component_type in system.get_components() {
+            signature.insert(self.get_component_id(*component_type));
+        }
+        let handle = SystemHandle(self.next_system_handle);
+        self.next_system_handle += 1;
+        let mut entry = SystemEntry {
+            handle: handle,
+            stage: stage,
+            enabled: true,
+            system: Box::new(system),
+            signature: signature,
+            last_run: Cell::new(0),
+        };
+        // pick up the entities that were created before the system
+        for index in self.active_entities.iter() {
+            if entry.signature.is_subset(self.entities[index].get_mask()) {
+                entry.system.add_entity(Entity::new(index, self.entities[index].get_generation()));
+            }
+        }
+        self.systems.push(entry);
+        self.sort_systems();
+        return handle;
+    }
+
+    pub fn remove_system(&mut self, system: SystemHandle) -> bool {
+        match self.systems.iter().position(|entry| entry.handle == system) {
+            Some(position) => {
+                self.systems.remove(position);
+                self.system_orderings.retain(|&(first, second)| first != system && second != system);
+                self.sort_systems();
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Makes `system` run before `other`. Only systems in the same stage can be ordered.
+    pub fn run_before(&mut self, system: SystemHandle, other: SystemHandle) {
+        self.system_orderings.push((system, other));
+        self.sort_systems();
+    }
+
+    /// Makes `system` run after `other`. Only systems in the same stage can be ordered.
+    pub fn run_after(&mut self, system: SystemHandle, other: SystemHandle) {
+        self.run_before(other, system);
+    }
+
+    /// Disabled systems keep tracking their entities but are skipped when processing.
+    pub fn set_system_enabled(&mut self, system: SystemHandle, enabled: bool) {
+        for entry in &mut self.systems {
+            if entry.handle == system {
+                entry.enabled = enabled;
             }
         }
     }
+
+    pub fn pause_system(&mut self, system: SystemHandle) {
+        self.set_system_enabled(system, false);
+    }
+
+    pub fn resume_system(&mut self, system: SystemHandle) {
+        self.set_system_enabled(system, true);
+    }
+
+    pub fn is_system_enabled(&self, system: SystemHandle) -> bool {
+        self.systems.iter().any(|entry| entry.handle == system && entry.enabled)
+    }
+
+    /// Orders the systems by stage, then by the before/after constraints, then by insertion order.
+    fn sort_systems(&mut self) {
+        let mut order = Vec::new();
+        for stage in Stage::all().iter() {
+            let mut pending = (0..self.systems.len())
+                .filter(|&i| self.systems[i].stage == *stage)
+                .collect::<Vec<usize>>();
+            while !pending.is_empty() {
+                let systems = &self.systems;
+                let orderings = &self.system_orderings;
+                let ready = pending.iter().position(|&i| {
+                    !orderings.iter().any(|&(first, second)| {
+                        second == systems[i].handle && pending.iter().any(|&j| systems[j].handle == first)
+                    })
+                });
+                let position = match ready {
+                    Some(position) => position,
+                    None => {
+                        Log::error("Cycle in the system ordering constraints, falling back to insertion order");
+                        0
+                    }
+                };
+                order.push(pending.remove(position));
+            }
+        }
+        self.system_order = order;
+    }
+
+    /// Buffer for changes that have to wait until the World can be modified again.
+    pub fn commands(&self) -> &CommandBuffer<T> {
+        &self.commands
+    }
+
+    /// Applies the recorded commands in order, including the ones recorded while applying them.
+    pub fn apply_commands(&mut self) {
+        loop {
+            let commands = self.commands.drain();
+            if commands.is_empty() {
+                break;
+            }
+            for command in commands {
+                command(self);
+            }
+        }
+    }
+
+    /// Stores a singleton shared by the systems, like the delta time or the texture manager.
+    pub fn insert_resource<R: Any>(&mut self, resource: R) {
+        self.resources.insert(resource);
+    }
+
+    pub fn remove_resource<R: Any>(&mut self) -> Option<R> {
+        self.resources.remove::<R>()
+    }
+
+    pub fn has_resource<R: Any>(&self) -> bool {
+        self.resources.contains::<R>()
+    }
+
+    pub fn resource<R: Any>(&self) -> Option<Ref<R>> {
+        self.resources.get::<R>()
+    }
+
+    pub fn resource_mut<R: Any>(&self) -> Option<RefMut<R>> {
+        self.resources.get_mut::<R>()
+    }
+
+    /// Creates the channel for events of type E, if it doesn't exist yet.
+    pub fn add_event<E: Any>(&mut self) {
+        if !self.events.contains_key(&TypeId::of::<E>()) {
+            self.events.insert(TypeId::of::<E>(), Box::new(Events::<E>::new()));
+        }
+    }
+
+    pub fn get_events<E: Any>(&self) -> Option<&Events<E>> {
+        match self.events.get(&TypeId::of::<E>()) {
+            Some(events) => events.as_any().downcast_ref::<Events<E>>(),
+            None => None
+        }
+    }
+
+    pub fn send_event<E: Any>(&self, event: E) {
+        match self.get_events::<E>() {
+            Some(events) => events.send(event),
+            None => {
+                Log::warning("Event sent on a channel that hasn't been added to the world");
+            }
+        }
+    }
+
+    /// Panics if the channel for E hasn't been added with `add_event`.
+    pub fn event_reader<E: Any>(&self) -> EventReader<E> {
+        self.get_events::<E>().expect("Event channel not added to the world").reader()
+    }
+
+    pub fn read_events<E: Any + Clone>(&self, reader: &EventReader<E>) -> Vec<E> {
+        match self.get_events::<E>() {
+            Some(events) => events.read(reader),
+            None => Vec::new()
+        }
+    }
+
+    /// Drops the events that have been read by everyone or are two updates old.
+    /// Called once per frame.
+    pub fn update_events(&mut self) {
+        for events in self.events.values_mut() {
+            events.update();
+        }
+    }
+
+    /// Runs the enabled systems of the given stage.
+    /// Each system sees the changes made since its previous run.
+    pub fn process_stage(&self, stage: Stage, dt: f32, scene: &Scene<T>, user_data: &mut T) {
+        for index in &self.system_order {
+            let entry = &self.systems[*index];
+            if entry.stage != stage || !entry.enabled {
+                continue;
+            }
+            self.last_run_tick.set(entry.last_run.get());
+            // render systems draw from the back to the front
+            let sorted;
+            let entities = match stage {
+                Stage::Render => {
+                    sorted = scene.get_entities_in_draw_order(entry.system.get_entities());
+                    &sorted[..]
+                },
+                _ => entry.system.get_entities()
+            };
+            for entity in entities {
+                entry.system.process(*entity, dt, scene, user_data);
+            }
+            entry.last_run.set(self.change_tick.get());
+            self.change_tick.set(self.change_tick.get() + 1);
+        }
+        self.last_run_tick.set(self.frame_tick);
+    }
+
+    /// Runs every stage in order.
+    pub fn process(&self, dt: f32, scene: &Scene<T>, user_data: &mut T) {
+        for stage in Stage::all().iter() {
+            self.process_stage(*stage, dt, scene, user_data);
+        }
+    }
 }
 
 pub trait SystemData {
@@ -139,14 +1221,17 @@ pub trait SystemData {
 }
 
 pub trait System<T>: Any {
-    fn get_entities(&self) -> Vec<Entity>;
+    /// Entities currently matching the system's components, kept up to date by the World.
+    fn get_entities(&self) -> &[Entity];
     fn process(&self, entity: Entity, dt: f32, scene: &Scene<T>, user_data: &mut T);
-    fn get_components(&self) -> Vec<TypeId>;
+    /// Components an entity needs to have to be processed by the system.
+    fn get_components(&self) -> &[TypeId];
     fn add_entity(&mut self, entity: Entity);
+    fn remove_entity(&mut self, entity: Entity);
 }
 
 pub struct BaseSystem {
-    watch: BitSet,
+    members: BitSet,
     entities: Vec<Entity>,
     components: Vec<TypeId>,
 }
@@ -154,18 +1239,12 @@ pub struct BaseSystem {
 impl BaseSystem {
     pub fn new() -> Self {
         BaseSystem {
-            watch: BitSet::new(),
+            members: BitSet::new(),
             entities: Vec::new(),
             components: Vec::new(),
         }
     }
 
-    /*
-    pub fn watch_component(&mut self, component_id: usize) {
-        self.watch.insert(component_id);
-    }
-    */
-
     pub fn watch_component<C: Component>(&mut self) {
         self.components.push(TypeId::of::<C>());
     }
@@ -174,16 +1253,24 @@ impl BaseSystem {
         println!("BaseSystem process() called");
     }
 
-    pub fn get_components(&self) -> Vec<TypeId> {
-        return self.components.clone(); // TODO: this is very bad for performance
+    pub fn get_components(&self) -> &[TypeId] {
+        return &self.components;
     }
 
-    pub fn get_entities(&self) -> Vec<Entity> {
-        return self.entities.clone(); // TODO: this is very bad for performance
+    pub fn get_entities(&self) -> &[Entity] {
+        return &self.entities;
     }
 
     pub fn add_entity(&mut self, entity: Entity) {
-        self.entities.push(entity);
+        if self.members.insert(entity.get_index()) {
+            self.entities.push(entity);
+        }
+    }
+
+    pub fn remove_entity(&mut self, entity: Entity) {
+        if self.members.remove(entity.get_index()) {
+            self.entities.retain(|e| *e != entity);
+        }
     }
 }
 
@@ -291,4 +1378,370 @@ impl EntityList {
         }
     }
 }
-*/
\ No newline at end of file
+*/
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commandbuffer::EntityBuilder;
+
+    /// Moves every entity one unit to the right each frame. Entities spawn a new one when
+    /// they reach a multiple of 4 and are destroyed once they get to 6.
+    struct MoveSystem {
+        base: BaseSystem,
+    }
+
+    impl System<u32> for MoveSystem {
+        fn get_entities(&self) -> &[Entity] {
+            self.base.get_entities()
+        }
+
+        fn process(&self, entity: Entity, _dt: f32, scene: &Scene<u32>, user_data: &mut u32) {
+            *user_data += 1;
+            let position = *scene.get_component::<TransformComponent>(entity).unwrap().get_position();
+            let x = position.x + 1.0;
+            scene.commands().push(move |world: &mut World<u32>| {
+                world.with_component_mut::<TransformComponent, _, _>(entity, |transform| {
+                    transform.set_position(x, position.y);
+                });
+            });
+            if x as i32 % 4 == 0 {
+                let mut transform = TransformComponent::new();
+                transform.set_position(0.0, position.y + 1.0);
+                scene.commands().spawn(EntityBuilder::new().with(transform).with_tag(1).with_name("spawned"));
+            }
+            if x >= 6.0 {
+                scene.commands().despawn(entity);
+            }
+        }
+
+        fn get_components(&self) -> &[TypeId] {
+            self.base.get_components()
+        }
+
+        fn add_entity(&mut self, entity: Entity) {
+            self.base.add_entity(entity);
+        }
+
+        fn remove_entity(&mut self, entity: Entity) {
+            self.base.remove_entity(entity);
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct State {
+        entities: Vec<(Entity, (f32, f32), u32, Option<String>)>,
+        tagged: Vec<Vec<Entity>>,
+        spawned: Vec<Entity>,
+        generations: Vec<u32>,
+        free_entities: Vec<usize>,
+    }
+
+    fn state(world: &World<u32>) -> State {
+        State {
+            entities: world.get_entities().into_iter().map(|entity| {
+                let position = *world.get_component_for_entity::<TransformComponent>(entity).unwrap().get_position();
+                (entity, (position.x, position.y), world.get_tags(entity), world.get_name(entity).map(String::from))
+            }).collect(),
+            tagged: (0..3).map(|tag| world.get_entities_with_tag(tag).to_vec()).collect(),
+            spawned: world.find_all_by_name("spawned").to_vec(),
+            generations: world.entities.iter().map(|entity_data| entity_data.get_generation()).collect(),
+            free_entities: world.free_entities.clone(),
+        }
+    }
+
+    fn step(scene: &mut Scene<u32>, frames: usize) {
+        let mut processed = 0;
+        for _ in 0..frames {
+            scene.before_update(1.0, &mut processed);
+            scene.update(1.0, &mut processed);
+            scene.after_update(1.0, &mut processed);
+        }
+    }
+
+    #[test]
+    fn restore_replays_the_same_frames() {
+        let mut scene: Scene<u32> = Scene::new(32);
+        scene.register_snapshot_component::<TransformComponent>();
+        let mut system = MoveSystem { base: BaseSystem::new() };
+        system.base.watch_component::<TransformComponent>();
+        scene.add_system(system);
+        let mut entities = Vec::new();
+        for &x in [0.0, 2.0, 3.0, 4.0].iter() {
+            let entity = scene.create_entity();
+            let mut transform = TransformComponent::new();
+            transform.set_position(x, 0.0);
+            scene.add_component_to_entity(entity, transform);
+            entities.push(entity);
+        }
+        assert!(scene.set_unique_name(entities[0], "player"));
+        scene.add_tag(entities[1], 2);
+        step(&mut scene, 2);
+
+        let snapshot = scene.snapshot();
+        let before = state(scene.get_world());
+        assert!(!before.free_entities.is_empty());
+        // the third entity gets to 6 during the next frame
+        assert!(scene.is_alive(entities[2]));
+        step(&mut scene, 2);
+        let after = state(scene.get_world());
+        assert!(!scene.is_alive(entities[2]));
+        assert_ne!(after.free_entities, before.free_entities);
+
+        scene.restore(&snapshot);
+        assert_eq!(state(scene.get_world()), before);
+        assert!(scene.is_alive(entities[2]));
+        assert_eq!(scene.find_by_name("player"), Some(entities[0]));
+        step(&mut scene, 2);
+        assert_eq!(state(scene.get_world()), after);
+    }
+
+    #[test]
+    fn destroyed_entities_lose_their_components() {
+        let mut world: World<()> = World::new();
+        world.register_component_with_storage::<TransformComponent>();
+        let a = world.create_entity();
+        let b = world.create_entity();
+        world.add_component_to_entity(a, TransformComponent::new());
+        let mut transform = TransformComponent::new();
+        transform.set_position(3.0, 4.0);
+        world.add_component_to_entity(b, transform);
+        world.destroy_entity(a);
+        assert!(!world.is_alive(a));
+        assert!(world.get_component_for_entity::<TransformComponent>(a).is_none());
+        assert_eq!(world.get_component_for_entity::<TransformComponent>(b).unwrap().get_position().x, 3.0);
+        // the slot is reused with a new generation and without the old component
+        let c = world.create_entity();
+        assert_eq!(c.get_index(), a.get_index());
+        assert!(c.get_generation() != a.get_generation());
+        assert!(world.get_component_for_entity::<TransformComponent>(c).is_none());
+        world.remove_component_from_entity::<TransformComponent>(b);
+        assert!(world.get_component_for_entity::<TransformComponent>(b).is_none());
+        assert!(world.is_alive(b));
+        world.remove_component_from_entity::<TransformComponent>(a);
+    }
+
+    /// Counts the entities it processes in the user data.
+    struct CountSystem {
+        base: BaseSystem,
+    }
+
+    impl CountSystem {
+        fn watching_transforms() -> Self {
+            let mut system = CountSystem { base: BaseSystem::new() };
+            system.base.watch_component::<TransformComponent>();
+            system
+        }
+    }
+
+    impl System<u32> for CountSystem {
+        fn get_entities(&self) -> &[Entity] {
+            self.base.get_entities()
+        }
+
+        fn process(&self, _entity: Entity, _dt: f32, _scene: &Scene<u32>, user_data: &mut u32) {
+            *user_data += 1;
+        }
+
+        fn get_components(&self) -> &[TypeId] {
+            self.base.get_components()
+        }
+
+        fn add_entity(&mut self, entity: Entity) {
+            self.base.add_entity(entity);
+        }
+
+        fn remove_entity(&mut self, entity: Entity) {
+            self.base.remove_entity(entity);
+        }
+    }
+
+    fn count_processed(scene: &Scene<u32>) -> u32 {
+        let mut processed = 0;
+        scene.update(0.0, &mut processed);
+        processed
+    }
+
+    #[test]
+    fn systems_follow_the_components_of_the_entities() {
+        use imagecomponent::ImageComponent;
+        let mut scene: Scene<u32> = Scene::new(32);
+        scene.register_component::<TransformComponent>();
+        scene.register_component::<ImageComponent>();
+        let a = scene.create_entity();
+        scene.add_component_to_entity(a, TransformComponent::new());
+        let mut system = CountSystem::watching_transforms();
+        system.base.watch_component::<ImageComponent>();
+        scene.add_system(system);
+        assert_eq!(count_processed(&scene), 0);
+        scene.add_component_to_entity(a, ImageComponent::new());
+        assert_eq!(count_processed(&scene), 1);
+        let b = scene.create_entity();
+        scene.add_component_to_entity(b, ImageComponent::new());
+        scene.add_component_to_entity(b, TransformComponent::new());
+        assert_eq!(count_processed(&scene), 2);
+        // adding a component twice doesn't add the entity twice
+        scene.add_component_to_entity(b, TransformComponent::new());
+        assert_eq!(count_processed(&scene), 2);
+        scene.remove_component_from_entity::<ImageComponent>(a);
+        assert_eq!(count_processed(&scene), 1);
+        scene.destroy_entity(b);
+        assert_eq!(count_processed(&scene), 0);
+    }
+
+    /// Writes its id in the user data for every entity it processes.
+    struct RecordSystem {
+        base: BaseSystem,
+        id: u32,
+    }
+
+    impl RecordSystem {
+        fn new(id: u32) -> Self {
+            let mut system = RecordSystem { base: BaseSystem::new(), id: id };
+            system.base.watch_component::<TransformComponent>();
+            system
+        }
+    }
+
+    impl System<Vec<u32>> for RecordSystem {
+        fn get_entities(&self) -> &[Entity] {
+            self.base.get_entities()
+        }
+
+        fn process(&self, _entity: Entity, _dt: f32, _scene: &Scene<Vec<u32>>, user_data: &mut Vec<u32>) {
+            user_data.push(self.id);
+        }
+
+        fn get_components(&self) -> &[TypeId] {
+            self.base.get_components()
+        }
+
+        fn add_entity(&mut self, entity: Entity) {
+            self.base.add_entity(entity);
+        }
+
+        fn remove_entity(&mut self, entity: Entity) {
+            self.base.remove_entity(entity);
+        }
+    }
+
+    fn run_order(scene: &Scene<Vec<u32>>) -> Vec<u32> {
+        let mut order = Vec::new();
+        scene.process(0.0, &mut order);
+        order
+    }
+
+    #[test]
+    fn systems_run_by_stage_then_by_ordering_constraints() {
+        let mut scene: Scene<Vec<u32>> = Scene::new(32);
+        scene.register_component::<TransformComponent>();
+        let entity = scene.create_entity();
+        scene.add_component_to_entity(entity, TransformComponent::new());
+        let render = scene.add_system_to_stage(Stage::Render, RecordSystem::new(1));
+        let first = scene.add_system(RecordSystem::new(2));
+        let second = scene.add_system(RecordSystem::new(3));
+        scene.add_system_to_stage(Stage::PreUpdate, RecordSystem::new(4));
+        let post_update = scene.add_system_to_stage(Stage::PostUpdate, RecordSystem::new(5));
+        assert_eq!(run_order(&scene), vec![4, 2, 3, 5, 1]);
+        scene.run_before(second, first);
+        assert_eq!(run_order(&scene), vec![4, 3, 2, 5, 1]);
+        let third = scene.add_system(RecordSystem::new(6));
+        scene.run_after(first, third);
+        assert_eq!(run_order(&scene), vec![4, 3, 6, 2, 5, 1]);
+        scene.set_system_enabled(post_update, false);
+        scene.set_system_enabled(render, false);
+        assert_eq!(run_order(&scene), vec![4, 3, 6, 2]);
+        scene.set_system_enabled(render, true);
+        assert!(scene.remove_system(third));
+        assert!(!scene.remove_system(third));
+        assert_eq!(run_order(&scene), vec![4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn ordering_cycles_fall_back_to_insertion_order() {
+        let mut scene: Scene<Vec<u32>> = Scene::new(32);
+        scene.register_component::<TransformComponent>();
+        let entity = scene.create_entity();
+        scene.add_component_to_entity(entity, TransformComponent::new());
+        let first = scene.add_system(RecordSystem::new(1));
+        let second = scene.add_system(RecordSystem::new(2));
+        scene.run_before(first, second);
+        scene.run_before(second, first);
+        assert_eq!(run_order(&scene), vec![1, 2]);
+    }
+
+    #[test]
+    fn changes_are_tracked_until_the_trackers_are_cleared() {
+        let mut world: World<()> = World::new();
+        world.register_component_with_storage::<TransformComponent>();
+        let a = world.create_entity();
+        let b = world.create_entity();
+        world.add_component_to_entity(a, TransformComponent::new());
+        world.add_component_to_entity(b, TransformComponent::new());
+        assert!(world.is_added::<TransformComponent>(a));
+        assert_eq!(world.query::<&TransformComponent>().added::<TransformComponent>().count(), 2);
+        world.clear_trackers();
+        assert!(!world.is_added::<TransformComponent>(a));
+        assert!(!world.is_changed::<TransformComponent>(b));
+        // reading doesn't count as a change, borrowing mutably does
+        world.get_component_for_entity::<TransformComponent>(a);
+        world.get_component_mut::<TransformComponent>(b).unwrap().set_position(1.0, 1.0);
+        assert!(!world.is_changed::<TransformComponent>(a));
+        assert!(world.is_changed::<TransformComponent>(b));
+        assert_eq!(world.query::<&TransformComponent>().changed::<TransformComponent>().entities(), vec![b]);
+        world.clear_trackers();
+        assert!(!world.is_changed::<TransformComponent>(b));
+        assert_eq!(world.query::<&TransformComponent>().changed::<TransformComponent>().count(), 0);
+        world.query_mut::<&mut TransformComponent>().for_each(|_, _| {});
+        assert_eq!(world.query::<&TransformComponent>().changed::<TransformComponent>().count(), 2);
+        world.remove_component_from_entity::<TransformComponent>(a);
+        world.destroy_entity(b);
+        assert_eq!(world.removed_components::<TransformComponent>(), vec![a, b]);
+        world.clear_trackers();
+        assert!(world.removed_components::<TransformComponent>().is_empty());
+    }
+
+    #[test]
+    fn names_are_looked_up_and_cleaned_up() {
+        let mut world: World<()> = World::new();
+        let a = world.create_entity();
+        let b = world.create_entity();
+        let c = world.create_entity();
+        assert!(world.set_name(a, "enemy"));
+        assert!(world.set_name(b, "enemy"));
+        assert!(world.set_unique_name(c, "player"));
+        assert!(!world.set_name(a, "player"));
+        assert!(!world.set_unique_name(a, "enemy"));
+        assert_eq!(world.get_name(a), Some("enemy"));
+        assert_eq!(world.find_all_by_name("enemy"), &[a, b][..]);
+        assert_eq!(world.find_by_name("player"), Some(c));
+
+        // the slot of a destroyed entity comes back without its name
+        world.destroy_entity(a);
+        assert_eq!(world.find_all_by_name("enemy"), &[b][..]);
+        let d = world.create_entity();
+        assert_eq!(world.get_name(d), None);
+        assert!(world.set_name(d, "enemy"));
+        assert_eq!(world.find_all_by_name("enemy"), &[b, d][..]);
+        assert!(world.get_debug_label(d).starts_with("enemy ("));
+
+        // a unique name is free again once its entity is gone
+        world.destroy_entity(c);
+        assert!(world.set_name(b, "player"));
+        assert_eq!(world.find_all_by_name("enemy"), &[d][..]);
+        assert_eq!(world.find_all_by_name("player"), &[b][..]);
+    }
+
+    #[test]
+    fn failed_renames_leave_no_name() {
+        let mut world: World<()> = World::new();
+        let a = world.create_entity();
+        let b = world.create_entity();
+        assert!(world.set_unique_name(a, "player"));
+        assert!(!world.set_name(b, "player"));
+        assert_eq!(world.get_name(b), None);
+        assert!(world.find_all_by_name("").is_empty());
+        assert!(world.set_name(b, "enemy"));
+        assert!(!world.set_unique_name(b, "player"));
+        assert_eq!(world.get_name(b), Some("enemy"));
+    }
+}